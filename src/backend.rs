@@ -0,0 +1,195 @@
+mod fake;
+mod retry;
+mod steam;
+
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use color_eyre::eyre;
+use serde::{Deserialize, Serialize};
+
+pub use fake::FakeBackend;
+pub use retry::RetryingBackend;
+pub use steam::SteamBackend;
+
+use crate::{
+    cli::{BackendKind, PublishedFileVisibility},
+    config::{RetryPolicy, Timeouts},
+    workshop::{AppId, Tag},
+};
+
+/// Remote state of a workshop item, independent of the backend it was fetched from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemDetails {
+    pub item_id: u64,
+    pub app_id: u32,
+    pub title: String,
+    pub description: String,
+    pub visibility: PublishedFileVisibility,
+    pub tags: Vec<String>,
+    pub owner: u64,
+    pub time_created: u32,
+    pub time_updated: u32,
+    pub file_size: u64,
+    pub preview_url: Option<String>,
+    #[serde(default)]
+    pub additional_previews: Vec<AdditionalPreview>,
+    #[serde(default)]
+    pub statistics: ItemStatistics,
+}
+
+/// A preview shown in an item's gallery besides its primary preview image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AdditionalPreview {
+    Image {
+        url: String,
+        filename: String,
+    },
+    #[serde(rename = "youtube")]
+    YouTube {
+        video_id: String,
+    },
+    /// Sketchfab models, environment maps and such, which can only be added on the Steam website. Kept as is by
+    /// [`crate::preview::diff_previews`].
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewSource {
+    Image(PathBuf),
+    YouTube(String),
+}
+
+impl fmt::Display for PreviewSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Image(path) => write!(f, "{:?}", path),
+            Self::YouTube(video_id) => write!(f, "YouTube video {video_id}"),
+        }
+    }
+}
+
+/// Change to an item's additional previews. Indices refer to the previews before the update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewChange {
+    Add(PreviewSource),
+    Replace { index: u32, source: PreviewSource },
+    Remove { index: u32 },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ItemStatistics {
+    pub subscriptions: u64,
+    pub favorites: u64,
+    pub followers: u64,
+    pub upvotes: u32,
+    pub downvotes: u32,
+}
+
+/// Changes to be applied to a workshop item in a single submission.
+///
+/// Fields left as `None` are not touched on the remote item.
+#[derive(Debug, Clone, Default)]
+pub struct ItemUpdate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub visibility: Option<PublishedFileVisibility>,
+    pub tags: Option<Vec<Tag>>,
+    pub preview_path: Option<PathBuf>,
+    pub content_path: Option<PathBuf>,
+    pub change_note: Option<String>,
+    /// Replaces all values of each key on the item.
+    pub key_value_tags: BTreeMap<String, String>,
+    /// Keys whose key-value tags are removed from the item.
+    pub removed_key_value_tags: Vec<String>,
+    pub metadata: Option<String>,
+    /// Applied in order: replacements, then removals from the highest index, then additions.
+    pub preview_changes: Vec<PreviewChange>,
+    /// Steam API language code the title and description are meant for. The item's default language if `None`.
+    pub language: Option<String>,
+}
+
+/// https://partner.steamgames.com/doc/api/ISteamUGC#EItemUpdateStatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStage {
+    Invalid,
+    #[strum(serialize = "Preparing config")]
+    PreparingConfig,
+    #[strum(serialize = "Preparing content")]
+    PreparingContent,
+    #[strum(serialize = "Uploading content")]
+    UploadingContent,
+    #[strum(serialize = "Uploading preview")]
+    UploadingPreview,
+    #[strum(serialize = "Committing changes")]
+    CommittingChanges,
+}
+
+/// A single page of a user's published items.
+#[derive(Debug, Clone)]
+pub struct ItemPage {
+    pub items: Vec<ItemDetails>,
+    /// Total number of items across all pages.
+    pub total: u32,
+}
+
+/// Number of results per page for UGC queries.
+///
+/// https://partner.steamgames.com/doc/api/ISteamUGC#kNumUGCResultsPerPage
+pub const ITEMS_PER_PAGE: u32 = 50;
+
+#[derive(Debug, Clone, Copy)]
+pub struct UpdateProgress {
+    pub stage: UpdateStage,
+    pub bytes_processed: u64,
+    pub bytes_total: u64,
+}
+
+/// Everything the create/update flows need from Steam, so they can be run against something other than a live
+/// Steam client.
+pub trait WorkshopBackend {
+    /// Returns the id of the new item, and whether the user still needs to accept the Workshop legal agreement.
+    fn create_item(&self, app_id: AppId) -> eyre::Result<(steamworks::PublishedFileId, bool)>;
+    fn query_item(&self, item_id: u64) -> eyre::Result<Option<ItemDetails>>;
+    /// Same as [`Self::query_item`], but with the title and description in `language`. Those fall back to the
+    /// default language if the item has no localization for it.
+    fn query_item_localized(
+        &self,
+        item_id: u64,
+        language: &str,
+    ) -> eyre::Result<Option<ItemDetails>>;
+    /// Items published by the logged-in user for `app_id`, most recently updated first. `page` starts from 1.
+    fn query_user_items(&self, app_id: AppId, page: u32) -> eyre::Result<ItemPage>;
+    /// `on_progress` is called periodically while the submission is in flight.
+    fn submit_update(
+        &self,
+        app_id: AppId,
+        item_id: u64,
+        update: &ItemUpdate,
+        on_progress: &mut dyn FnMut(UpdateProgress),
+    ) -> eyre::Result<(steamworks::PublishedFileId, bool)>;
+    fn delete_item(&self, item_id: u64) -> eyre::Result<()>;
+}
+
+/// Everything needed to set up a backend, apart from the AppId it's for.
+#[derive(Debug, Clone)]
+pub struct BackendOptions {
+    pub kind: BackendKind,
+    /// State file for [`FakeBackend`].
+    pub fake_state_path: Option<PathBuf>,
+    pub timeouts: Timeouts,
+    pub retry: RetryPolicy,
+}
+
+pub fn init(options: &BackendOptions, app_id: AppId) -> eyre::Result<Box<dyn WorkshopBackend>> {
+    let backend: Box<dyn WorkshopBackend> = match options.kind {
+        BackendKind::Steam => Box::new(SteamBackend::init(app_id, options.timeouts.clone())?),
+        BackendKind::Fake => Box::new(FakeBackend::new(options.fake_state_path.clone())?),
+    };
+
+    Ok(match options.retry.retries {
+        0 => backend,
+        _ => Box::new(RetryingBackend::new(backend, options.retry.clone())),
+    })
+}
@@ -0,0 +1,634 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use better_default::Default;
+use color_eyre::eyre::{self, Context, ContextCompat};
+use relative_path::PathExt as RelPathExt;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::{
+    AdditionalPreview, ItemDetails, ItemPage, ItemUpdate, PreviewChange, PreviewSource,
+    UpdateProgress, UpdateStage, WorkshopBackend, ITEMS_PER_PAGE,
+};
+use crate::{
+    cli::PublishedFileVisibility,
+    workshop::{check_metadata, is_valid_preview_type, AppId},
+};
+
+/// SteamId reported as the owner of every item created through the fake backend.
+const FAKE_OWNER_STEAM_ID: u64 = 76561197960287930;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FakeItem {
+    #[serde(flatten)]
+    pub details: ItemDetails,
+    /// Content files of the last submission, relative to the content root.
+    pub files: Vec<String>,
+    pub change_notes: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub key_value_tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub metadata: String,
+    /// Keyed by language code.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub localization: BTreeMap<String, FakeLocalization>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FakeLocalization {
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FakeState {
+    #[default(1)]
+    pub next_item_id: u64,
+    /// Reported on every create and update, to simulate a user who hasn't accepted the Workshop legal agreement.
+    pub needs_legal_agreement: bool,
+    pub items: Vec<FakeItem>,
+}
+
+/// Keeps workshop items in memory, optionally persisted to a JSON state file so that separate invocations of the tool
+/// share them. Meant for exercising the create/update flows without a Steam client, e.g. on CI.
+pub struct FakeBackend {
+    state_path: Option<PathBuf>,
+    state: RefCell<FakeState>,
+}
+
+impl FakeBackend {
+    pub fn new(state_path: Option<PathBuf>) -> eyre::Result<Self> {
+        let state = match &state_path {
+            Some(path) if path.is_file() => serde_json::from_str(&fs_err::read_to_string(path)?)
+                .with_context(|| format!("Failed to parse fake backend state {:?}", path))?,
+            _ => FakeState::default(),
+        };
+
+        Ok(Self {
+            state_path,
+            state: RefCell::new(state),
+        })
+    }
+
+    fn persist(&self) -> eyre::Result<()> {
+        if let Some(path) = &self.state_path {
+            fs_err::write(path, serde_json::to_string_pretty(&*self.state.borrow())?)?;
+        }
+        Ok(())
+    }
+}
+
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+fn list_content_files(content_path: &Path) -> eyre::Result<(Vec<String>, u64)> {
+    let mut files = vec![];
+    let mut size = 0;
+    for entry in ignore::WalkBuilder::new(content_path)
+        .standard_filters(false)
+        .build()
+    {
+        let entry = entry?;
+        if entry.file_type().is_some_and(|it| it.is_file()) {
+            files.push(entry.path().relative_to(content_path)?.to_string());
+            size += entry.metadata()?.len();
+        }
+    }
+    files.sort();
+
+    Ok((files, size))
+}
+
+fn additional_preview(source: PreviewSource) -> eyre::Result<AdditionalPreview> {
+    Ok(match source {
+        PreviewSource::Image(path) => AdditionalPreview::Image {
+            url: format!(
+                "file://{}",
+                path.canonicalize()?
+                    .to_str()
+                    .context("Preview path is not valid UTF-8")?
+            ),
+            filename: path
+                .file_name()
+                .and_then(|it| it.to_str())
+                .context("Preview file name is not valid UTF-8")?
+                .to_owned(),
+        },
+        PreviewSource::YouTube(video_id) => AdditionalPreview::YouTube { video_id },
+    })
+}
+
+/// Same order as Steam: replacements, then removals from the highest index, then additions.
+fn apply_preview_changes(
+    previews: &mut Vec<AdditionalPreview>,
+    changes: Vec<PreviewChange>,
+) -> eyre::Result<()> {
+    let mut removed = vec![];
+    let mut added = vec![];
+    for change in changes {
+        match change {
+            PreviewChange::Replace { index, source } => {
+                *previews
+                    .get_mut(index as usize)
+                    .ok_or(steamworks::SteamError::InvalidParameter)? = additional_preview(source)?;
+            }
+            PreviewChange::Remove { index } => removed.push(index as usize),
+            PreviewChange::Add(source) => added.push(additional_preview(source)?),
+        }
+    }
+
+    removed.sort_unstable();
+    for index in removed.into_iter().rev() {
+        if index >= previews.len() {
+            Err(steamworks::SteamError::InvalidParameter)?;
+        }
+        previews.remove(index);
+    }
+    previews.extend(added);
+
+    Ok(())
+}
+
+impl WorkshopBackend for FakeBackend {
+    fn create_item(&self, app_id: AppId) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
+        let item_id;
+        {
+            let mut state = self.state.borrow_mut();
+            item_id = state.next_item_id;
+            state.next_item_id += 1;
+            state.items.push(FakeItem {
+                details: ItemDetails {
+                    item_id,
+                    app_id: app_id.0,
+                    title: String::new(),
+                    description: String::new(),
+                    visibility: PublishedFileVisibility::Private,
+                    tags: vec![],
+                    owner: FAKE_OWNER_STEAM_ID,
+                    time_created: now(),
+                    time_updated: now(),
+                    file_size: 0,
+                    preview_url: None,
+                    additional_previews: vec![],
+                    statistics: Default::default(),
+                },
+                files: vec![],
+                change_notes: vec![],
+                key_value_tags: BTreeMap::new(),
+                metadata: String::new(),
+                localization: BTreeMap::new(),
+            });
+        }
+        self.persist()?;
+
+        info!(item_id, "Created fake workshop item");
+
+        Ok((
+            steamworks::PublishedFileId(item_id),
+            self.state.borrow().needs_legal_agreement,
+        ))
+    }
+
+    fn query_item(&self, item_id: u64) -> eyre::Result<Option<ItemDetails>> {
+        Ok(self
+            .state
+            .borrow()
+            .items
+            .iter()
+            .find(|it| it.details.item_id == item_id)
+            .map(|it| it.details.clone()))
+    }
+
+    fn query_item_localized(
+        &self,
+        item_id: u64,
+        language: &str,
+    ) -> eyre::Result<Option<ItemDetails>> {
+        Ok(self
+            .state
+            .borrow()
+            .items
+            .iter()
+            .find(|it| it.details.item_id == item_id)
+            .map(|it| match it.localization.get(language) {
+                Some(localization) => ItemDetails {
+                    title: localization.title.clone(),
+                    description: localization.description.clone(),
+                    ..it.details.clone()
+                },
+                None => it.details.clone(),
+            }))
+    }
+
+    fn query_user_items(&self, app_id: AppId, page: u32) -> eyre::Result<ItemPage> {
+        let state = self.state.borrow();
+        let mut items = state
+            .items
+            .iter()
+            .filter(|it| it.details.app_id == app_id.0 && it.details.owner == FAKE_OWNER_STEAM_ID)
+            .map(|it| it.details.clone())
+            .collect::<Vec<_>>();
+        items.sort_by(|a, b| b.time_updated.cmp(&a.time_updated));
+
+        let total = items.len() as u32;
+        let items = items
+            .into_iter()
+            .skip((page.saturating_sub(1) * ITEMS_PER_PAGE) as usize)
+            .take(ITEMS_PER_PAGE as usize)
+            .collect();
+
+        Ok(ItemPage { items, total })
+    }
+
+    fn submit_update(
+        &self,
+        _app_id: AppId,
+        item_id: u64,
+        update: &ItemUpdate,
+        on_progress: &mut dyn FnMut(UpdateProgress),
+    ) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
+        let content = update
+            .content_path
+            .as_deref()
+            .map(list_content_files)
+            .transpose()?;
+        if let Some(preview_path) = &update.preview_path {
+            is_valid_preview_type(preview_path)?;
+        }
+        if let Some(metadata) = &update.metadata {
+            check_metadata(metadata)?;
+        }
+        for change in &update.preview_changes {
+            if let PreviewChange::Add(PreviewSource::Image(path))
+            | PreviewChange::Replace {
+                source: PreviewSource::Image(path),
+                ..
+            } = change
+            {
+                is_valid_preview_type(path)?;
+            }
+        }
+
+        let bytes_total = content.as_ref().map(|(_, size)| *size).unwrap_or_default();
+        for (stage, bytes_processed) in [
+            (UpdateStage::PreparingConfig, 0),
+            (UpdateStage::PreparingContent, 0),
+            (UpdateStage::UploadingContent, bytes_total),
+            (UpdateStage::CommittingChanges, bytes_total),
+        ] {
+            on_progress(UpdateProgress {
+                stage,
+                bytes_processed,
+                bytes_total,
+            });
+        }
+
+        {
+            let mut state = self.state.borrow_mut();
+            let item = state
+                .items
+                .iter_mut()
+                .find(|it| it.details.item_id == item_id)
+                .ok_or(steamworks::SteamError::FileNotFound)?;
+
+            match &update.language {
+                Some(language) => {
+                    let localization =
+                        item.localization
+                            .entry(language.clone())
+                            .or_insert_with(|| FakeLocalization {
+                                title: item.details.title.clone(),
+                                description: item.details.description.clone(),
+                            });
+                    if let Some(title) = &update.title {
+                        localization.title = title.clone();
+                    }
+                    if let Some(description) = &update.description {
+                        localization.description = description.clone();
+                    }
+                }
+                None => {
+                    if let Some(title) = &update.title {
+                        item.details.title = title.clone();
+                    }
+                    if let Some(description) = &update.description {
+                        item.details.description = description.clone();
+                    }
+                }
+            }
+            if let Some(visibility) = update.visibility {
+                item.details.visibility = visibility;
+            }
+            if let Some(tags) = &update.tags {
+                item.details.tags = tags.iter().map(|it| it.to_string()).collect();
+            }
+            if let Some(preview_path) = &update.preview_path {
+                item.details.preview_url = Some(format!(
+                    "file://{}",
+                    preview_path
+                        .canonicalize()?
+                        .to_str()
+                        .context("Preview path is not valid UTF-8")?
+                ));
+            }
+            for key in &update.removed_key_value_tags {
+                item.key_value_tags.remove(key);
+            }
+            item.key_value_tags.extend(update.key_value_tags.clone());
+            if let Some(metadata) = &update.metadata {
+                item.metadata = metadata.clone();
+            }
+            apply_preview_changes(
+                &mut item.details.additional_previews,
+                update.preview_changes.clone(),
+            )?;
+            if let Some((files, size)) = content {
+                item.files = files;
+                item.details.file_size = size;
+            }
+            if let Some(change_note) = &update.change_note {
+                item.change_notes.push(change_note.clone());
+            }
+            item.details.time_updated = now();
+        }
+        self.persist()?;
+
+        Ok((
+            steamworks::PublishedFileId(item_id),
+            self.state.borrow().needs_legal_agreement,
+        ))
+    }
+
+    fn delete_item(&self, item_id: u64) -> eyre::Result<()> {
+        {
+            let mut state = self.state.borrow_mut();
+            let index = state
+                .items
+                .iter()
+                .position(|it| it.details.item_id == item_id)
+                .ok_or(steamworks::SteamError::FileNotFound)?;
+            state.items.remove(index);
+        }
+        self.persist()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::PreviewConfig, preview::diff_previews, workshop::Tag};
+
+    const APP_ID: AppId = AppId(480);
+
+    #[test]
+    fn create_update_and_query() -> eyre::Result<()> {
+        let state_dir = tempfile::tempdir()?;
+        let state_path = state_dir.path().join("state.json");
+        let content = tempfile::tempdir()?;
+        fs_err::create_dir(content.path().join("data"))?;
+        fs_err::write(content.path().join("mod.txt"), "v1")?;
+        fs_err::write(content.path().join("data/a.bin"), "aaa")?;
+
+        let backend = FakeBackend::new(Some(state_path.clone()))?;
+        let (item_id, needs_legal_agreement) = backend.create_item(APP_ID)?;
+        assert!(!needs_legal_agreement);
+
+        let mut stages = vec![];
+        backend.submit_update(
+            APP_ID,
+            item_id.0,
+            &ItemUpdate {
+                title: Some("My Item".to_owned()),
+                visibility: Some(PublishedFileVisibility::Public),
+                tags: Some(vec![Tag::new("mod")?]),
+                content_path: Some(content.path().to_path_buf()),
+                change_note: Some("Initial release".to_owned()),
+                ..Default::default()
+            },
+            &mut |it| stages.push(it.stage),
+        )?;
+        assert_eq!(stages.last(), Some(&UpdateStage::CommittingChanges));
+
+        // A separate invocation, sharing the item through the state file
+        let backend = FakeBackend::new(Some(state_path))?;
+        backend.submit_update(
+            APP_ID,
+            item_id.0,
+            &ItemUpdate {
+                description: Some("Now with a description".to_owned()),
+                change_note: Some("Describe the item".to_owned()),
+                ..Default::default()
+            },
+            &mut |_| {},
+        )?;
+
+        let item = backend.query_item(item_id.0)?.context("Item is gone")?;
+        assert_eq!(item.app_id, APP_ID.0);
+        assert_eq!(item.title, "My Item");
+        assert_eq!(item.description, "Now with a description");
+        assert!(matches!(item.visibility, PublishedFileVisibility::Public));
+        assert_eq!(item.tags, ["mod"]);
+        assert_eq!(item.file_size, 5);
+
+        let state = backend.state.borrow();
+        assert_eq!(state.items[0].files, ["data/a.bin", "mod.txt"]);
+        assert_eq!(
+            state.items[0].change_notes,
+            ["Initial release", "Describe the item"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn update_of_missing_item_fails() -> eyre::Result<()> {
+        let backend = FakeBackend::new(None)?;
+        assert!(backend
+            .submit_update(APP_ID, 1, &ItemUpdate::default(), &mut |_| {})
+            .is_err());
+        assert!(backend.query_item(1)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn delete_removes_item() -> eyre::Result<()> {
+        let backend = FakeBackend::new(None)?;
+        let (item_id, _) = backend.create_item(APP_ID)?;
+        backend.delete_item(item_id.0)?;
+        assert!(backend.query_item(item_id.0)?.is_none());
+        assert!(backend.delete_item(item_id.0).is_err());
+        Ok(())
+    }
+
+    fn preview(video_id: &str) -> AdditionalPreview {
+        AdditionalPreview::YouTube {
+            video_id: video_id.to_owned(),
+        }
+    }
+
+    fn video(video_id: &str) -> PreviewSource {
+        PreviewSource::YouTube(video_id.to_owned())
+    }
+
+    #[test]
+    fn preview_changes_apply_in_steam_order() -> eyre::Result<()> {
+        let mut previews = vec![preview("a"), preview("b"), preview("c")];
+        apply_preview_changes(
+            &mut previews,
+            vec![
+                PreviewChange::Add(video("d")),
+                PreviewChange::Remove { index: 0 },
+                PreviewChange::Replace {
+                    index: 2,
+                    source: video("e"),
+                },
+                PreviewChange::Remove { index: 1 },
+            ],
+        )?;
+        assert_eq!(previews, [preview("e"), preview("d")]);
+
+        assert!(
+            apply_preview_changes(&mut previews, vec![PreviewChange::Remove { index: 2 }]).is_err()
+        );
+        Ok(())
+    }
+
+    fn video_id(c: char) -> String {
+        c.to_string().repeat(11)
+    }
+
+    fn configured_videos(ids: &str) -> Vec<PreviewConfig> {
+        ids.chars()
+            .map(|it| PreviewConfig::YouTube(video_id(it)))
+            .collect()
+    }
+
+    fn remote_videos(ids: &str) -> Vec<AdditionalPreview> {
+        ids.chars().map(|it| preview(&video_id(it))).collect()
+    }
+
+    /// Diffs `desired` against `remote` and applies the changes to it, checking that nothing is left to change after.
+    fn sync_previews(
+        desired: &[PreviewConfig],
+        remote: &mut Vec<AdditionalPreview>,
+        content_path: &Path,
+    ) -> eyre::Result<Vec<PreviewChange>> {
+        let changes = diff_previews(desired, remote, content_path, None)?;
+        apply_preview_changes(remote, changes.clone())?;
+        assert!(diff_previews(desired, remote, content_path, None)?.is_empty());
+        Ok(changes)
+    }
+
+    #[test]
+    fn diff_previews_replaces_in_place_and_removes_tail() -> eyre::Result<()> {
+        let mut remote = remote_videos("abc");
+        assert!(sync_previews(&configured_videos("abc"), &mut remote, Path::new("."))?.is_empty());
+
+        let changes = sync_previews(&configured_videos("ad"), &mut remote, Path::new("."))?;
+        assert_eq!(
+            changes,
+            [
+                PreviewChange::Replace {
+                    index: 1,
+                    source: video(&video_id('d')),
+                },
+                PreviewChange::Remove { index: 2 },
+            ]
+        );
+        assert_eq!(remote, remote_videos("ad"));
+        Ok(())
+    }
+
+    #[test]
+    fn diff_previews_stops_replacing_at_kind_mismatch() -> eyre::Result<()> {
+        let mut remote = vec![
+            AdditionalPreview::Image {
+                url: "https://example.com/shot.png".to_owned(),
+                filename: "shot.png".to_owned(),
+            },
+            preview(&video_id('a')),
+        ];
+        let changes = sync_previews(&configured_videos("ab"), &mut remote, Path::new("."))?;
+        assert_eq!(
+            changes,
+            [
+                PreviewChange::Remove { index: 0 },
+                PreviewChange::Remove { index: 1 },
+                PreviewChange::Add(video(&video_id('a'))),
+                PreviewChange::Add(video(&video_id('b'))),
+            ]
+        );
+        assert_eq!(remote, remote_videos("ab"));
+        Ok(())
+    }
+
+    #[test]
+    fn diff_previews_keeps_other_kinds() -> eyre::Result<()> {
+        let mut remote = vec![
+            preview(&video_id('a')),
+            AdditionalPreview::Other,
+            preview(&video_id('b')),
+        ];
+        let changes = sync_previews(&configured_videos("acd"), &mut remote, Path::new("."))?;
+        assert_eq!(
+            changes,
+            [
+                PreviewChange::Replace {
+                    index: 2,
+                    source: video(&video_id('c')),
+                },
+                PreviewChange::Add(video(&video_id('d'))),
+            ]
+        );
+        assert_eq!(
+            remote,
+            [
+                preview(&video_id('a')),
+                AdditionalPreview::Other,
+                preview(&video_id('c')),
+                preview(&video_id('d')),
+            ]
+        );
+
+        sync_previews(&[], &mut remote, Path::new("."))?;
+        assert_eq!(remote, [AdditionalPreview::Other]);
+        Ok(())
+    }
+
+    #[test]
+    fn diff_previews_compares_images_by_file_name() -> eyre::Result<()> {
+        let content = tempfile::tempdir()?;
+        image::RgbImage::new(160, 90).save(content.path().join("shot.png"))?;
+        let desired = [PreviewConfig::Image("shot.png".into())];
+
+        let mut remote = vec![AdditionalPreview::Image {
+            url: "https://example.com/shot.png".to_owned(),
+            filename: "shot.png".to_owned(),
+        }];
+        assert!(sync_previews(&desired, &mut remote, content.path())?.is_empty());
+
+        let mut remote = vec![AdditionalPreview::Image {
+            url: "https://example.com/old.png".to_owned(),
+            filename: "old.png".to_owned(),
+        }];
+        assert_eq!(
+            sync_previews(&desired, &mut remote, content.path())?,
+            [PreviewChange::Replace {
+                index: 0,
+                source: PreviewSource::Image(content.path().join("shot.png").canonicalize()?),
+            }]
+        );
+        Ok(())
+    }
+}
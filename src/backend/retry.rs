@@ -0,0 +1,136 @@
+use std::{thread, time::Duration};
+
+use color_eyre::{eyre, owo_colors::OwoColorize};
+use tracing::warn;
+
+use super::{ItemDetails, ItemPage, ItemUpdate, UpdateProgress, WorkshopBackend};
+use crate::{config::RetryPolicy, workshop::AppId};
+
+fn steam_error(report: &eyre::Report) -> Option<&steamworks::SteamError> {
+    report
+        .chain()
+        .find_map(|it| it.downcast_ref::<steamworks::SteamError>())
+}
+
+/// Whether the error is likely to go away by trying again later.
+fn is_retryable(report: &eyre::Report) -> bool {
+    steam_error(report).is_some_and(|it| {
+        matches!(
+            it,
+            steamworks::SteamError::Busy
+                | steamworks::SteamError::Timeout
+                | steamworks::SteamError::ServiceUnavailable
+                | steamworks::SteamError::RateLimitExceeded
+                | steamworks::SteamError::NoConnection
+                | steamworks::SteamError::IOFailure
+                | steamworks::SteamError::RemoteDisconnect
+        )
+    })
+}
+
+/// Same as [`is_retryable`], minus the errors after which the request may have gone through regardless, e.g. when
+/// only its response was lost.
+fn is_retryable_without_side_effects(report: &eyre::Report) -> bool {
+    is_retryable(report)
+        && !steam_error(report).is_some_and(|it| {
+            matches!(
+                it,
+                steamworks::SteamError::Timeout
+                    | steamworks::SteamError::IOFailure
+                    | steamworks::SteamError::RemoteDisconnect
+            )
+        })
+}
+
+/// Retries failed operations of the inner backend according to the [`RetryPolicy`], with exponential backoff.
+pub struct RetryingBackend {
+    inner: Box<dyn WorkshopBackend>,
+    policy: RetryPolicy,
+}
+
+impl RetryingBackend {
+    pub fn new(inner: Box<dyn WorkshopBackend>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    fn retry<T>(&self, operation: &str, f: impl FnMut() -> eyre::Result<T>) -> eyre::Result<T> {
+        self.retry_if(operation, is_retryable, f)
+    }
+
+    fn retry_if<T>(
+        &self,
+        operation: &str,
+        is_retryable: fn(&eyre::Report) -> bool,
+        mut f: impl FnMut() -> eyre::Result<T>,
+    ) -> eyre::Result<T> {
+        let mut backoff = Duration::from_secs(self.policy.initial_backoff_secs);
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(err) if attempt < self.policy.retries && is_retryable(&err) => {
+                    attempt += 1;
+                    warn!(operation, attempt, %err, ?backoff, "Retrying after transient failure");
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "[!] {operation} failed: {err}. Retrying in {}s ({attempt}/{})...",
+                            backoff.as_secs(),
+                            self.policy.retries
+                        )
+                        .yellow()
+                    );
+
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(Duration::from_secs(self.policy.max_backoff_secs));
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl WorkshopBackend for RetryingBackend {
+    fn create_item(&self, app_id: AppId) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
+        // Retrying after the item may have been created already would leave an empty duplicate behind
+        self.retry_if("Creating item", is_retryable_without_side_effects, || {
+            self.inner.create_item(app_id)
+        })
+    }
+
+    fn query_item(&self, item_id: u64) -> eyre::Result<Option<ItemDetails>> {
+        self.retry("Querying item", || self.inner.query_item(item_id))
+    }
+
+    fn query_item_localized(
+        &self,
+        item_id: u64,
+        language: &str,
+    ) -> eyre::Result<Option<ItemDetails>> {
+        self.retry("Querying item", || {
+            self.inner.query_item_localized(item_id, language)
+        })
+    }
+
+    fn query_user_items(&self, app_id: AppId, page: u32) -> eyre::Result<ItemPage> {
+        self.retry("Querying items", || {
+            self.inner.query_user_items(app_id, page)
+        })
+    }
+
+    fn submit_update(
+        &self,
+        app_id: AppId,
+        item_id: u64,
+        update: &ItemUpdate,
+        on_progress: &mut dyn FnMut(UpdateProgress),
+    ) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
+        self.retry("Submitting update", || {
+            self.inner
+                .submit_update(app_id, item_id, update, &mut *on_progress)
+        })
+    }
+
+    fn delete_item(&self, item_id: u64) -> eyre::Result<()> {
+        self.retry("Deleting item", || self.inner.delete_item(item_id))
+    }
+}
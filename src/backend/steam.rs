@@ -0,0 +1,286 @@
+mod raw;
+
+use std::sync::mpsc;
+
+use color_eyre::eyre::{self, bail};
+use itertools::Itertools;
+use tracing::error;
+
+use super::{
+    ItemDetails, ItemPage, ItemStatistics, ItemUpdate, UpdateProgress, UpdateStage, WorkshopBackend,
+};
+use crate::{
+    config::Timeouts,
+    ext::{
+        run_callbacks_blocking, SteamworksClient, SteamworksSingleClient, UGCBlockingExt,
+        UpdateHandleBlockingExt,
+    },
+    workshop::{self, AppId},
+};
+
+/// Talks to a running Steam client through steamworks.
+pub struct SteamBackend {
+    client: SteamworksClient,
+    single: SteamworksSingleClient,
+    timeouts: Timeouts,
+}
+
+impl SteamBackend {
+    pub fn init(app_id: AppId, timeouts: Timeouts) -> eyre::Result<Self> {
+        let (client, single) = workshop::steamworks_client_init(app_id)?;
+        Ok(Self {
+            client,
+            single,
+            timeouts,
+        })
+    }
+
+    /// Returns the details of all returned results, along with the total number of matching items.
+    fn fetch_query(
+        &self,
+        query: steamworks::QueryHandle<steamworks::ClientManager>,
+    ) -> eyre::Result<(Vec<ItemDetails>, u32)> {
+        let (tx, rx) = mpsc::channel();
+        query.include_long_desc(true).fetch(move |result| {
+            _ = tx
+                .send(result.map(|it| {
+                    (
+                        (0..it.returned_results())
+                            .filter_map(|index| item_details(&it, index))
+                            .collect_vec(),
+                        it.total_results(),
+                    )
+                }))
+                .inspect_err(|e| error!(%e));
+        });
+
+        Ok(run_callbacks_blocking(
+            &self.single,
+            &rx,
+            self.timeouts.query(),
+            || false,
+        )??)
+    }
+}
+
+impl From<steamworks::UpdateStatus> for UpdateStage {
+    fn from(value: steamworks::UpdateStatus) -> Self {
+        match value {
+            steamworks::UpdateStatus::Invalid => Self::Invalid,
+            steamworks::UpdateStatus::PreparingConfig => Self::PreparingConfig,
+            steamworks::UpdateStatus::PreparingContent => Self::PreparingContent,
+            steamworks::UpdateStatus::UploadingContent => Self::UploadingContent,
+            steamworks::UpdateStatus::UploadingPreviewFile => Self::UploadingPreview,
+            steamworks::UpdateStatus::CommittingChanges => Self::CommittingChanges,
+        }
+    }
+}
+
+fn item_details(results: &steamworks::QueryResults<'_>, index: u32) -> Option<ItemDetails> {
+    let item = results.get(index)?;
+    Some(ItemDetails {
+        item_id: item.published_file_id.0,
+        app_id: item.consumer_app_id.map(|it| it.0).unwrap_or_default(),
+        title: item.title,
+        description: item.description,
+        visibility: item.visibility.into(),
+        tags: item.tags,
+        owner: item.owner.raw(),
+        time_created: item.time_created,
+        time_updated: item.time_updated,
+        file_size: item.file_size.into(),
+        preview_url: results.preview_url(index),
+        // Only filled in by `query_item`, see there
+        additional_previews: vec![],
+        statistics: ItemStatistics {
+            subscriptions: results
+                .statistic(index, steamworks::UGCStatisticType::Subscriptions)
+                .unwrap_or_default(),
+            favorites: results
+                .statistic(index, steamworks::UGCStatisticType::Favorites)
+                .unwrap_or_default(),
+            followers: results
+                .statistic(index, steamworks::UGCStatisticType::Followers)
+                .unwrap_or_default(),
+            upvotes: item.num_upvotes,
+            downvotes: item.num_downvotes,
+        },
+    })
+}
+
+impl WorkshopBackend for SteamBackend {
+    fn create_item(&self, app_id: AppId) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
+        self.client.ugc().create_item_blocking(
+            &self.single,
+            app_id.into(),
+            steamworks::FileType::Community,
+            self.timeouts.create(),
+        )
+    }
+
+    fn query_item(&self, item_id: u64) -> eyre::Result<Option<ItemDetails>> {
+        let (items, _) = self.fetch_query(
+            self.client
+                .ugc()
+                .query_item(steamworks::PublishedFileId(item_id))?,
+        )?;
+        let Some(mut item) = items.into_iter().next() else {
+            return Ok(None);
+        };
+
+        // steamworks doesn't bind `GetQueryUGCAdditionalPreview`
+        if let Some(queried) = raw::query_item(item_id, None, self.timeouts.query())? {
+            item.additional_previews = queried.additional_previews;
+        }
+        Ok(Some(item))
+    }
+
+    fn query_item_localized(
+        &self,
+        item_id: u64,
+        language: &str,
+    ) -> eyre::Result<Option<ItemDetails>> {
+        let Some(item) = self.query_item(item_id)? else {
+            return Ok(None);
+        };
+        // steamworks doesn't bind `SetLanguage` for queries
+        let Some(localized) = raw::query_item(item_id, Some(language), self.timeouts.query())?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(ItemDetails {
+            title: localized.title,
+            description: localized.description,
+            ..item
+        }))
+    }
+
+    fn query_user_items(&self, app_id: AppId, page: u32) -> eyre::Result<ItemPage> {
+        let (items, total) = self.fetch_query(self.client.ugc().query_user(
+            self.client.user().steam_id().account_id(),
+            steamworks::UserList::Published,
+            steamworks::UGCType::Items,
+            steamworks::UserListOrder::LastUpdatedDesc,
+            steamworks::AppIDs::ConsumerAppId(app_id.into()),
+            page,
+        )?)?;
+        Ok(ItemPage { items, total })
+    }
+
+    fn submit_update(
+        &self,
+        app_id: AppId,
+        item_id: u64,
+        update: &ItemUpdate,
+        on_progress: &mut dyn FnMut(UpdateProgress),
+    ) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
+        if let Some(language) = &update.language {
+            if update.visibility.is_some()
+                || update.tags.is_some()
+                || update.preview_path.is_some()
+                || update.content_path.is_some()
+                || !update.key_value_tags.is_empty()
+                || !update.removed_key_value_tags.is_empty()
+                || update.metadata.is_some()
+                || !update.preview_changes.is_empty()
+            {
+                bail!("Only the title and description of an item can be updated in a specific language");
+            }
+
+            // steamworks doesn't bind `SetItemUpdateLanguage`
+            let needs_legal_agreement = raw::submit_update(
+                app_id,
+                item_id,
+                &raw::RawUpdate {
+                    language: Some(language),
+                    title: update.title.as_deref(),
+                    description: update.description.as_deref(),
+                    preview_changes: &[],
+                    change_note: update.change_note.as_deref(),
+                },
+                self.timeouts.submit(),
+                on_progress,
+            )?;
+            return Ok((steamworks::PublishedFileId(item_id), needs_legal_agreement));
+        }
+        let mut handle = self
+            .client
+            .ugc()
+            .start_item_update(app_id.into(), steamworks::PublishedFileId(item_id));
+
+        if let Some(title) = &update.title {
+            handle = handle.title(title);
+        }
+        if let Some(description) = &update.description {
+            handle = handle.description(description);
+        }
+        if let Some(visibility) = update.visibility {
+            handle = handle.visibility(visibility.into());
+        }
+        if let Some(tags) = &update.tags {
+            handle = handle.tags(tags.iter().collect_vec(), false);
+        }
+        if let Some(preview_path) = &update.preview_path {
+            handle = handle.preview_path(preview_path);
+        }
+        for key in update
+            .removed_key_value_tags
+            .iter()
+            .chain(update.key_value_tags.keys())
+        {
+            handle = handle.remove_key_value_tags(key);
+        }
+        for (key, value) in &update.key_value_tags {
+            handle = handle.add_key_value_tag(key, value);
+        }
+        if let Some(metadata) = &update.metadata {
+            handle = handle.metadata(metadata);
+        }
+        if let Some(content_path) = &update.content_path {
+            handle = handle.content_path(content_path); // Symlinked files don't work unfortunately
+        }
+
+        let (file_id, needs_legal_agreement) = handle.submit_blocking(
+            &self.single,
+            // This is such a horrible API, like `Option<&str>`? Seriously?
+            update.change_note.as_deref(),
+            self.timeouts.submit(),
+            |stage, bytes_processed, bytes_total| {
+                on_progress(UpdateProgress {
+                    stage: stage.into(),
+                    bytes_processed,
+                    bytes_total,
+                })
+            },
+        )?;
+        if update.preview_changes.is_empty() {
+            return Ok((file_id, needs_legal_agreement));
+        }
+
+        // steamworks doesn't bind `AddItemPreviewFile` and the like. After the main submission, so that retrying
+        // a failed one doesn't apply the preview changes twice.
+        let needs_legal_agreement_on_previews = raw::submit_update(
+            app_id,
+            item_id,
+            &raw::RawUpdate {
+                preview_changes: &update.preview_changes,
+                ..Default::default()
+            },
+            self.timeouts.submit(),
+            on_progress,
+        )?;
+        Ok((
+            file_id,
+            needs_legal_agreement || needs_legal_agreement_on_previews,
+        ))
+    }
+
+    fn delete_item(&self, item_id: u64) -> eyre::Result<()> {
+        self.client.ugc().delete_item_blocking(
+            &self.single,
+            steamworks::PublishedFileId(item_id),
+            self.timeouts.delete(),
+        )
+    }
+}
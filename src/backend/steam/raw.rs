@@ -0,0 +1,407 @@
+//! ISteamUGC calls that steamworks doesn't bind, made through `steamworks::sys`.
+//!
+//! steamworks keeps its update and query handles private, so updates and queries needing these calls are made from
+//! start to finish here instead.
+
+use std::{
+    ffi::{c_char, CString},
+    mem,
+    time::Duration,
+};
+
+use color_eyre::eyre::{self, bail, ContextCompat};
+use steamworks::sys;
+
+use crate::{
+    backend::{AdditionalPreview, PreviewChange, PreviewSource, UpdateProgress, UpdateStage},
+    ext::poll_blocking,
+    workshop::AppId,
+};
+
+/// https://partner.steamgames.com/doc/api/ISteamUtils#SteamAPICallCompleted_t
+const API_CALL_COMPLETED_CALLBACK_ID: i32 = 703;
+/// https://partner.steamgames.com/doc/api/ISteamUGC#SteamUGCQueryCompleted_t
+const QUERY_COMPLETED_CALLBACK_ID: i32 = 3401;
+/// https://partner.steamgames.com/doc/api/ISteamUGC#SubmitItemUpdateResult_t
+const SUBMIT_ITEM_UPDATE_RESULT_CALLBACK_ID: i32 = 3404;
+
+const INVALID_HANDLE: u64 = u64::MAX;
+const INVALID_API_CALL: sys::SteamAPICall_t = 0;
+/// Size of the buffers receiving the URL and file name of additional previews.
+const PREVIEW_FIELD_MAX_CHARS: usize = 1024;
+
+fn ugc() -> *mut sys::ISteamUGC {
+    unsafe {
+        sys::SteamInternal_FindOrCreateUserInterface(
+            sys::SteamAPI_GetHSteamUser(),
+            sys::STEAMUGC_INTERFACE_VERSION.as_ptr().cast(),
+        )
+        .cast()
+    }
+}
+
+fn c_string(s: &str) -> eyre::Result<CString> {
+    Ok(CString::new(s)?)
+}
+
+fn from_c_chars(chars: &[c_char]) -> String {
+    // Steam always null-terminates these, but the buffer's end is the limit regardless
+    let bytes = chars
+        .iter()
+        .take_while(|it| **it != 0)
+        .map(|it| *it as u8)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn check_result(result: sys::EResult) -> eyre::Result<()> {
+    if result != sys::EResult::k_EResultOK {
+        Err(steamworks::SteamError::from(result))?;
+    }
+    Ok(())
+}
+
+/// Waits for the result `T` of an asynchronous call made outside of steamworks, dispatching callbacks by hand.
+///
+/// steamworks drops the results of calls it didn't make itself, so its callbacks aren't run meanwhile, and any
+/// other callbacks arriving are dropped instead. Nothing else is in flight while this tool waits on Steam.
+///
+/// `on_tick` is called on every poll, and returns whether the call made progress.
+fn wait_for_call_result<T: Copy>(
+    call: sys::SteamAPICall_t,
+    callback_id: i32,
+    timeout: Option<Duration>,
+    mut on_tick: impl FnMut() -> bool,
+) -> eyre::Result<T> {
+    if call == INVALID_API_CALL {
+        bail!("Steam refused to start the request");
+    }
+
+    let result = poll_blocking(timeout, || unsafe {
+        let pipe = sys::SteamAPI_GetHSteamPipe();
+        sys::SteamAPI_ManualDispatch_RunFrame(pipe);
+
+        let mut result = None;
+        let mut message = mem::zeroed::<sys::CallbackMsg_t>();
+        while sys::SteamAPI_ManualDispatch_GetNextCallback(pipe, &mut message) {
+            if message.m_iCallback == API_CALL_COMPLETED_CALLBACK_ID {
+                let completed = std::ptr::read_unaligned(
+                    message.m_pubParam as *const sys::SteamAPICallCompleted_t,
+                );
+                if { completed.m_hAsyncCall } == call {
+                    let mut value = mem::MaybeUninit::<T>::zeroed();
+                    let mut failed = false;
+                    if sys::SteamAPI_ManualDispatch_GetAPICallResult(
+                        pipe,
+                        call,
+                        value.as_mut_ptr().cast(),
+                        mem::size_of::<T>() as _,
+                        callback_id,
+                        &mut failed,
+                    ) {
+                        result = Some((!failed).then(|| value.assume_init()));
+                    }
+                }
+            }
+            sys::SteamAPI_ManualDispatch_FreeLastCallback(pipe);
+        }
+
+        Ok((result, on_tick()))
+    })?;
+
+    Ok(result.ok_or(steamworks::SteamError::IOFailure)?)
+}
+
+/// Details of an item that steamworks can't query.
+pub struct QueriedItem {
+    pub title: String,
+    pub description: String,
+    pub additional_previews: Vec<AdditionalPreview>,
+}
+
+/// Queries a single item, with the title and description in `language` if given.
+pub fn query_item(
+    item_id: u64,
+    language: Option<&str>,
+    timeout: Option<Duration>,
+) -> eyre::Result<Option<QueriedItem>> {
+    let ugc = ugc();
+    let mut item_ids = [item_id];
+    let handle = unsafe {
+        sys::SteamAPI_ISteamUGC_CreateQueryUGCDetailsRequest(ugc, item_ids.as_mut_ptr(), 1)
+    };
+    if handle == INVALID_HANDLE {
+        bail!("Failed to create a query for item {item_id}");
+    }
+
+    struct ReleaseGuard(*mut sys::ISteamUGC, sys::UGCQueryHandle_t);
+    impl Drop for ReleaseGuard {
+        fn drop(&mut self) {
+            unsafe { sys::SteamAPI_ISteamUGC_ReleaseQueryUGCRequest(self.0, self.1) };
+        }
+    }
+    let _guard = ReleaseGuard(ugc, handle);
+
+    let language = language.map(c_string).transpose()?;
+    let call = unsafe {
+        sys::SteamAPI_ISteamUGC_SetReturnLongDescription(ugc, handle, true);
+        sys::SteamAPI_ISteamUGC_SetReturnAdditionalPreviews(ugc, handle, true);
+        if let Some(language) = &language {
+            sys::SteamAPI_ISteamUGC_SetLanguage(ugc, handle, language.as_ptr());
+        }
+        sys::SteamAPI_ISteamUGC_SendQueryUGCRequest(ugc, handle)
+    };
+
+    let completed = wait_for_call_result::<sys::SteamUGCQueryCompleted_t>(
+        call,
+        QUERY_COMPLETED_CALLBACK_ID,
+        timeout,
+        || false,
+    )?;
+    check_result(completed.m_eResult)?;
+    if { completed.m_unNumResultsReturned } == 0 {
+        return Ok(None);
+    }
+
+    let mut details = unsafe { mem::zeroed::<sys::SteamUGCDetails_t>() };
+    if !unsafe { sys::SteamAPI_ISteamUGC_GetQueryUGCResult(ugc, handle, 0, &mut details) } || {
+        details.m_eResult
+    }
+        != sys::EResult::k_EResultOK
+    {
+        return Ok(None);
+    }
+
+    let mut additional_previews = vec![];
+    for index in
+        0..unsafe { sys::SteamAPI_ISteamUGC_GetQueryUGCNumAdditionalPreviews(ugc, handle, 0) }
+    {
+        let mut url_or_video_id = [0 as c_char; PREVIEW_FIELD_MAX_CHARS];
+        let mut filename = [0 as c_char; PREVIEW_FIELD_MAX_CHARS];
+        let mut preview_type = sys::EItemPreviewType::k_EItemPreviewType_Image;
+        if !unsafe {
+            sys::SteamAPI_ISteamUGC_GetQueryUGCAdditionalPreview(
+                ugc,
+                handle,
+                0,
+                index,
+                url_or_video_id.as_mut_ptr(),
+                url_or_video_id.len() as _,
+                filename.as_mut_ptr(),
+                filename.len() as _,
+                &mut preview_type,
+            )
+        } {
+            bail!("Failed to read additional preview #{index} of item {item_id}");
+        }
+
+        additional_previews.push(match preview_type {
+            sys::EItemPreviewType::k_EItemPreviewType_Image => AdditionalPreview::Image {
+                url: from_c_chars(&url_or_video_id),
+                filename: from_c_chars(&filename),
+            },
+            sys::EItemPreviewType::k_EItemPreviewType_YouTubeVideo => AdditionalPreview::YouTube {
+                video_id: from_c_chars(&url_or_video_id),
+            },
+            // Still listed, as skipping them would throw off the indices of the ones after
+            _ => AdditionalPreview::Other,
+        });
+    }
+
+    Ok(Some(QueriedItem {
+        title: from_c_chars(&details.m_rgchTitle),
+        description: from_c_chars(&details.m_rgchDescription),
+        additional_previews,
+    }))
+}
+
+/// Changes made through [`submit_update`].
+#[derive(Debug, Default)]
+pub struct RawUpdate<'a> {
+    pub language: Option<&'a str>,
+    pub title: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub preview_changes: &'a [PreviewChange],
+    pub change_note: Option<&'a str>,
+}
+
+fn update_stage(status: sys::EItemUpdateStatus) -> UpdateStage {
+    match status {
+        sys::EItemUpdateStatus::k_EItemUpdateStatusPreparingConfig => UpdateStage::PreparingConfig,
+        sys::EItemUpdateStatus::k_EItemUpdateStatusPreparingContent => {
+            UpdateStage::PreparingContent
+        }
+        sys::EItemUpdateStatus::k_EItemUpdateStatusUploadingContent => {
+            UpdateStage::UploadingContent
+        }
+        sys::EItemUpdateStatus::k_EItemUpdateStatusUploadingPreviewFile => {
+            UpdateStage::UploadingPreview
+        }
+        sys::EItemUpdateStatus::k_EItemUpdateStatusCommittingChanges => {
+            UpdateStage::CommittingChanges
+        }
+        _ => UpdateStage::Invalid,
+    }
+}
+
+fn preview_source(source: &PreviewSource) -> eyre::Result<CString> {
+    match source {
+        PreviewSource::Image(path) => c_string(
+            path.to_str()
+                .with_context(|| format!("Preview path {:?} is not valid UTF-8", path))?,
+        ),
+        PreviewSource::YouTube(video_id) => c_string(video_id),
+    }
+}
+
+/// Same order as [`PreviewChange`]s are documented to apply in: replacements, then removals from the highest index,
+/// then additions.
+fn set_preview_changes(
+    ugc: *mut sys::ISteamUGC,
+    handle: sys::UGCUpdateHandle_t,
+    changes: &[PreviewChange],
+) -> eyre::Result<()> {
+    let mut removed = vec![];
+    let mut added = vec![];
+    for change in changes {
+        match change {
+            PreviewChange::Replace { index, source } => {
+                let value = preview_source(source)?;
+                let accepted = unsafe {
+                    match source {
+                        PreviewSource::Image(_) => sys::SteamAPI_ISteamUGC_UpdateItemPreviewFile(
+                            ugc,
+                            handle,
+                            *index,
+                            value.as_ptr(),
+                        ),
+                        PreviewSource::YouTube(_) => {
+                            sys::SteamAPI_ISteamUGC_UpdateItemPreviewVideo(
+                                ugc,
+                                handle,
+                                *index,
+                                value.as_ptr(),
+                            )
+                        }
+                    }
+                };
+                if !accepted {
+                    bail!("Steam rejected replacing additional preview #{index} with {source}");
+                }
+            }
+            PreviewChange::Remove { index } => removed.push(*index),
+            PreviewChange::Add(source) => added.push(source),
+        }
+    }
+
+    removed.sort_unstable();
+    for index in removed.into_iter().rev() {
+        if !unsafe { sys::SteamAPI_ISteamUGC_RemoveItemPreview(ugc, handle, index) } {
+            bail!("Steam rejected removing additional preview #{index}");
+        }
+    }
+    for source in added {
+        let value = preview_source(source)?;
+        let accepted = unsafe {
+            match source {
+                PreviewSource::Image(_) => sys::SteamAPI_ISteamUGC_AddItemPreviewFile(
+                    ugc,
+                    handle,
+                    value.as_ptr(),
+                    sys::EItemPreviewType::k_EItemPreviewType_Image,
+                ),
+                PreviewSource::YouTube(_) => {
+                    sys::SteamAPI_ISteamUGC_AddItemPreviewVideo(ugc, handle, value.as_ptr())
+                }
+            }
+        };
+        if !accepted {
+            bail!("Steam rejected adding additional preview {source}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the user still needs to accept the Workshop legal agreement.
+///
+/// `timeout` counts from the last time the submission made progress.
+pub fn submit_update(
+    app_id: AppId,
+    item_id: u64,
+    update: &RawUpdate,
+    timeout: Option<Duration>,
+    on_progress: &mut dyn FnMut(UpdateProgress),
+) -> eyre::Result<bool> {
+    let ugc = ugc();
+    let handle = unsafe { sys::SteamAPI_ISteamUGC_StartItemUpdate(ugc, app_id.0, item_id) };
+    if handle == INVALID_HANDLE {
+        bail!("Failed to start an update of item {item_id}");
+    }
+
+    // Steam copies these when they're set, but they're kept alive until the submission regardless
+    let language = update.language.map(c_string).transpose()?;
+    let title = update.title.map(c_string).transpose()?;
+    let description = update.description.map(c_string).transpose()?;
+    let change_note = update.change_note.map(c_string).transpose()?;
+
+    unsafe {
+        if let Some(language) = &language {
+            if !sys::SteamAPI_ISteamUGC_SetItemUpdateLanguage(ugc, handle, language.as_ptr()) {
+                bail!("Steam rejected the update language {:?}", language);
+            }
+        }
+        if let Some(title) = &title {
+            if !sys::SteamAPI_ISteamUGC_SetItemTitle(ugc, handle, title.as_ptr()) {
+                bail!("Steam rejected the title {:?}", title);
+            }
+        }
+        if let Some(description) = &description {
+            if !sys::SteamAPI_ISteamUGC_SetItemDescription(ugc, handle, description.as_ptr()) {
+                bail!("Steam rejected the description");
+            }
+        }
+    }
+    set_preview_changes(ugc, handle, update.preview_changes)?;
+
+    // A null change note doesn't add an entry to the item's change notes
+    let call = unsafe {
+        sys::SteamAPI_ISteamUGC_SubmitItemUpdate(
+            ugc,
+            handle,
+            change_note
+                .as_ref()
+                .map_or(std::ptr::null(), |it| it.as_ptr()),
+        )
+    };
+    let mut last_progress = None;
+    let result = wait_for_call_result::<sys::SubmitItemUpdateResult_t>(
+        call,
+        SUBMIT_ITEM_UPDATE_RESULT_CALLBACK_ID,
+        timeout,
+        || {
+            let (mut bytes_processed, mut bytes_total) = (0, 0);
+            let status = unsafe {
+                sys::SteamAPI_ISteamUGC_GetItemUpdateProgress(
+                    ugc,
+                    handle,
+                    &mut bytes_processed,
+                    &mut bytes_total,
+                )
+            };
+            on_progress(UpdateProgress {
+                stage: update_stage(status),
+                bytes_processed,
+                bytes_total,
+            });
+
+            let progress = Some((status as u32, bytes_processed));
+            let made_progress = progress != last_progress;
+            last_progress = progress;
+            made_progress
+        },
+    )?;
+    check_result(result.m_eResult)?;
+
+    Ok(result.m_bUserNeedsToAcceptWorkshopLegalAgreement)
+}
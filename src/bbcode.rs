@@ -0,0 +1,237 @@
+use std::fmt::Write;
+
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+/// Ensures `out` ends with a line break, unless it's empty.
+fn end_line(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Appends `text` to `out`, keeping Steam from parsing any tags in it.
+fn push_text(out: &mut String, text: &str) {
+    if text.contains('[') {
+        _ = write!(out, "[noparse]{text}[/noparse]");
+    } else {
+        out.push_str(text);
+    }
+}
+
+/// Converts Markdown to the BBCode used by Steam for descriptions and change notes.
+///
+/// Headings below the third level become third level ones, as Steam has no smaller ones. Raw HTML is dropped, and text
+/// that looks like BBCode is kept as is.
+///
+/// https://steamcommunity.com/comment/Recommendation/formattinghelp
+pub fn markdown_to_bbcode(markdown: &str) -> String {
+    let mut out = String::new();
+    let mut list_depth = 0;
+    let mut in_table_head = false;
+    // Steam only shows the image, there's no alt text
+    let mut in_image = false;
+    // The parser splits text at brackets, which would leave tags in it split across `[noparse]` blocks
+    let mut text = String::new();
+
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    for event in Parser::new_ext(markdown, options) {
+        if !matches!(event, Event::Text(_) | Event::SoftBreak) {
+            push_text(&mut out, &text);
+            text.clear();
+        }
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Heading { level, .. } => {
+                    end_line(&mut out);
+                    out.push_str(match level {
+                        HeadingLevel::H1 => "[h1]",
+                        HeadingLevel::H2 => "[h2]",
+                        _ => "[h3]",
+                    });
+                }
+                Tag::List(first) => {
+                    end_line(&mut out);
+                    out.push_str(match first {
+                        Some(_) => "[olist]\n",
+                        None => "[list]\n",
+                    });
+                    list_depth += 1;
+                }
+                Tag::Item => out.push_str("[*]"),
+                Tag::BlockQuote(_) => {
+                    end_line(&mut out);
+                    out.push_str("[quote]");
+                }
+                Tag::CodeBlock(_) => {
+                    end_line(&mut out);
+                    out.push_str("[code]");
+                }
+                Tag::Table(_) => {
+                    end_line(&mut out);
+                    out.push_str("[table]\n");
+                }
+                Tag::TableHead => {
+                    in_table_head = true;
+                    out.push_str("[tr]");
+                }
+                Tag::TableRow => out.push_str("[tr]"),
+                Tag::TableCell => out.push_str(if in_table_head { "[th]" } else { "[td]" }),
+                Tag::Emphasis => out.push_str("[i]"),
+                Tag::Strong => out.push_str("[b]"),
+                Tag::Strikethrough => out.push_str("[strike]"),
+                Tag::Link { dest_url, .. } => {
+                    _ = write!(out, "[url={dest_url}]");
+                }
+                Tag::Image { dest_url, .. } => {
+                    in_image = true;
+                    _ = write!(out, "[img]{dest_url}[/img]");
+                }
+                _ => {}
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Paragraph => {
+                    // Items of loose lists are made of paragraphs, which shouldn't be spaced apart
+                    out.push_str(if list_depth > 0 { "\n" } else { "\n\n" });
+                }
+                TagEnd::Heading(level) => {
+                    out.push_str(match level {
+                        HeadingLevel::H1 => "[/h1]\n",
+                        HeadingLevel::H2 => "[/h2]\n",
+                        _ => "[/h3]\n",
+                    });
+                }
+                TagEnd::List(ordered) => {
+                    end_line(&mut out);
+                    out.push_str(if ordered { "[/olist]\n" } else { "[/list]\n" });
+                    list_depth -= 1;
+                    if list_depth == 0 {
+                        out.push('\n');
+                    }
+                }
+                TagEnd::Item => end_line(&mut out),
+                TagEnd::BlockQuote(_) => {
+                    // Its last paragraph already ended the line
+                    let trimmed = out.trim_end().len();
+                    out.truncate(trimmed);
+                    out.push_str("[/quote]\n\n");
+                }
+                TagEnd::CodeBlock => {
+                    // The code already ends with a line break
+                    out.push_str("[/code]\n\n");
+                }
+                TagEnd::Table => out.push_str("[/table]\n\n"),
+                TagEnd::TableHead => {
+                    in_table_head = false;
+                    out.push_str("[/tr]\n");
+                }
+                TagEnd::TableRow => out.push_str("[/tr]\n"),
+                TagEnd::TableCell => out.push_str(if in_table_head { "[/th]" } else { "[/td]" }),
+                TagEnd::Emphasis => out.push_str("[/i]"),
+                TagEnd::Strong => out.push_str("[/b]"),
+                TagEnd::Strikethrough => out.push_str("[/strike]"),
+                TagEnd::Link => out.push_str("[/url]"),
+                TagEnd::Image => in_image = false,
+                _ => {}
+            },
+            Event::Text(_) if in_image => {}
+            Event::Text(it) => text.push_str(&it),
+            Event::Code(code) => {
+                _ = write!(out, "[noparse]{code}[/noparse]");
+            }
+            // Markdown joins the lines of a paragraph, which is likely hard wrapped
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => out.push('\n'),
+            Event::Rule => {
+                end_line(&mut out);
+                out.push_str("[hr][/hr]\n");
+            }
+            _ => {}
+        }
+    }
+
+    push_text(&mut out, &text);
+
+    out.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headings() {
+        assert_eq!(
+            markdown_to_bbcode("# One\n## Two\n### Three\n#### Four"),
+            "[h1]One[/h1]\n[h2]Two[/h2]\n[h3]Three[/h3]\n[h3]Four[/h3]"
+        );
+    }
+
+    #[test]
+    fn paragraphs_and_breaks() {
+        assert_eq!(
+            markdown_to_bbcode("Hard\nwrapped\n\nHard break  \nhere"),
+            "Hard wrapped\n\nHard break\nhere"
+        );
+    }
+
+    #[test]
+    fn inline_formatting() {
+        assert_eq!(
+            markdown_to_bbcode("*i* **b** ~~s~~ `c` [link](https://example.com)"),
+            "[i]i[/i] [b]b[/b] [strike]s[/strike] [noparse]c[/noparse] [url=https://example.com]link[/url]"
+        );
+    }
+
+    #[test]
+    fn literal_tags_are_not_parsed() {
+        assert_eq!(
+            markdown_to_bbcode("Use [b] or [url=x] for that"),
+            "[noparse]Use [b] or [url=x] for that[/noparse]"
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            markdown_to_bbcode("- a\n- b\n  1. c\n  2. d\n\nAfter"),
+            "[list]\n[*]a\n[*]b\n[olist]\n[*]c\n[*]d\n[/olist]\n[/list]\n\nAfter"
+        );
+        assert_eq!(
+            markdown_to_bbcode("- a\n\n- b"),
+            "[list]\n[*]a\n[*]b\n[/list]"
+        );
+    }
+
+    #[test]
+    fn quotes_and_code_blocks() {
+        assert_eq!(
+            markdown_to_bbcode("> Quoted\n> text\n\n```\nlet x = [1];\n```"),
+            "[quote]Quoted text[/quote]\n\n[code][noparse]let x = [1];\n[/noparse][/code]"
+        );
+    }
+
+    #[test]
+    fn images() {
+        assert_eq!(
+            markdown_to_bbcode("![alt](https://example.com/a.png)"),
+            "[img]https://example.com/a.png[/img]"
+        );
+        assert_eq!(
+            markdown_to_bbcode("[![alt](https://example.com/a.png)](https://example.com)"),
+            "[url=https://example.com][img]https://example.com/a.png[/img][/url]"
+        );
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!(
+            markdown_to_bbcode("| A | B |\n|---|---|\n| 1 | 2 |"),
+            "[table]\n[tr][th]A[/th][th]B[/th][/tr]\n[tr][td]1[/td][td]2[/td][/tr]\n[/table]"
+        );
+    }
+
+    #[test]
+    fn rules() {
+        assert_eq!(markdown_to_bbcode("a\n\n---\n\nb"), "a\n\n[hr][/hr]\nb");
+    }
+}
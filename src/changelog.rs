@@ -0,0 +1,192 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use color_eyre::eyre::{self, bail, ContextCompat};
+use itertools::Itertools;
+
+use crate::{bbcode::markdown_to_bbcode, git};
+
+/// Max length of a change note.
+///
+/// https://partner.steamgames.com/doc/api/ISteamRemoteStorage#k_cchPublishedDocumentChangeDescriptionMax
+pub const CHANGE_NOTE_MAX_CHARS: usize = 8000;
+/// Commits collected for the change note when no upload has been recorded yet.
+const MAX_COMMITS_WITHOUT_RECORDED_UPLOAD: usize = 20;
+
+/// Where to take an item's change note from.
+#[derive(Debug, Clone)]
+pub enum ChangeLogSource {
+    /// Subjects of the commits since the last upload.
+    Git,
+    /// Topmost release section of a Markdown changelog, relative to the content directory.
+    Changelog(PathBuf),
+}
+
+impl FromStr for ChangeLogSource {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "git" => Ok(Self::Git),
+            Some(("changelog", file)) if !file.is_empty() => Ok(Self::Changelog(file.into())),
+            _ => bail!("Expected `git` or `changelog:FILE`, got `{s}`"),
+        }
+    }
+}
+
+/// Cuts the change note off at Steam's limit.
+pub fn truncate_change_note(change_note: String) -> String {
+    if change_note.chars().count() <= CHANGE_NOTE_MAX_CHARS {
+        return change_note;
+    }
+    change_note
+        .chars()
+        .take(CHANGE_NOTE_MAX_CHARS - 1)
+        .chain(['…'])
+        .collect()
+}
+
+/// The change note from `source` in Steam BBCode, or `None` if there's nothing new to note.
+///
+/// `last_uploaded_commit` is the commit that was checked out during the last successful upload.
+pub fn change_log_from(
+    source: &ChangeLogSource,
+    content_path: impl AsRef<Path>,
+    last_uploaded_commit: Option<&str>,
+) -> eyre::Result<Option<String>> {
+    let change_note = match source {
+        ChangeLogSource::Git => {
+            if !git::is_repo(content_path.as_ref()) {
+                bail!(
+                    "Content folder {:?} is not in a git repository",
+                    content_path.as_ref()
+                );
+            }
+
+            let subjects = git::commit_subjects_since(
+                content_path.as_ref(),
+                last_uploaded_commit,
+                MAX_COMMITS_WITHOUT_RECORDED_UPLOAD,
+            )?;
+            if subjects.is_empty() {
+                return Ok(None);
+            }
+            format!(
+                "[list]\n{}\n[/list]",
+                subjects.iter().map(|it| format!("[*]{it}")).join("\n")
+            )
+        }
+        ChangeLogSource::Changelog(file) => {
+            let changelog = fs_err::read_to_string(content_path.as_ref().join(file))?;
+            let section = topmost_release_section(&changelog)
+                .with_context(|| format!("No release section found in {:?}", file))?;
+            markdown_to_bbcode(section)
+        }
+    };
+
+    Ok(Some(truncate_change_note(change_note)))
+}
+
+/// Body of the first `## ` section of a changelog that isn't for unreleased changes, as in
+/// https://keepachangelog.com.
+fn topmost_release_section(changelog: &str) -> Option<&str> {
+    let mut in_code_block = false;
+    let mut start = None;
+    let mut offset = 0;
+    for line in changelog.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let is_heading = line.starts_with("## ") || line.starts_with("# ");
+        match start {
+            Some(start) if is_heading => return Some(changelog[start..line_start].trim()),
+            None if line.starts_with("## ") && !line.to_lowercase().contains("unreleased") => {
+                start = Some(offset);
+            }
+            _ => {}
+        }
+    }
+
+    start.map(|it| changelog[it..].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topmost_release_section_skips_unreleased() {
+        let changelog = "\
+# Changelog
+
+## [Unreleased]
+- Work in progress
+
+## [1.1.0] - 2024-01-01
+### Added
+- Thing
+```
+## Not a heading
+```
+
+## [1.0.0] - 2023-01-01
+- First release
+";
+        assert_eq!(
+            topmost_release_section(changelog),
+            Some("### Added\n- Thing\n```\n## Not a heading\n```")
+        );
+    }
+
+    #[test]
+    fn topmost_release_section_ends_at_any_heading() {
+        assert_eq!(
+            topmost_release_section("## 1.0\n- A\n# Other\n- B\n"),
+            Some("- A")
+        );
+        assert_eq!(topmost_release_section("## 1.0\n- A\n"), Some("- A"));
+    }
+
+    #[test]
+    fn topmost_release_section_without_release() {
+        assert_eq!(
+            topmost_release_section("# Changelog\n## Unreleased\n- A\n"),
+            None
+        );
+        assert_eq!(topmost_release_section(""), None);
+    }
+
+    #[test]
+    fn truncate_change_note_at_limit() {
+        let change_note = "a".repeat(CHANGE_NOTE_MAX_CHARS);
+        assert_eq!(truncate_change_note(change_note.clone()), change_note);
+
+        let truncated = truncate_change_note("ä".repeat(CHANGE_NOTE_MAX_CHARS + 1));
+        assert_eq!(truncated.chars().count(), CHANGE_NOTE_MAX_CHARS);
+        assert!(truncated.ends_with("ä…"));
+    }
+
+    #[test]
+    fn parse_change_log_source() -> eyre::Result<()> {
+        assert!(matches!(
+            "git".parse::<ChangeLogSource>()?,
+            ChangeLogSource::Git
+        ));
+        assert!(matches!(
+            "changelog:CHANGELOG.md".parse::<ChangeLogSource>()?,
+            ChangeLogSource::Changelog(path) if path == Path::new("CHANGELOG.md")
+        ));
+        assert!("changelog:".parse::<ChangeLogSource>().is_err());
+        assert!("svn".parse::<ChangeLogSource>().is_err());
+        Ok(())
+    }
+}
@@ -2,8 +2,13 @@ use std::{fmt::Debug, path::PathBuf};
 
 use clap::{builder::TypedValueParser, Parser, Subcommand, ValueEnum};
 use clio::ClioPath;
+use serde::{Deserialize, Serialize};
 
-use crate::workshop::{AppId, Tag};
+use crate::{
+    changelog::ChangeLogSource,
+    output::OutputFormat,
+    workshop::{parse_key_value_tag, AppId, Tag},
+};
 
 static IGNORE_HELP: &'static str = r#"By default, files and directories matching ignore patterns from files like `.ignore` and `.gitignore` are excluded."#;
 
@@ -12,6 +17,18 @@ static IGNORE_HELP: &'static str = r#"By default, files and directories matching
 pub struct Cli {
     #[arg(short = 'q', long)]
     pub no_prompt: bool,
+    /// Format of the output. `json` prints structured events as JSON lines on stdout.
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub output: OutputFormat,
+    /// Number of times to retry operations that failed for transient reasons. Overrides the config.
+    #[arg(long, value_name = "N", global = true)]
+    pub retries: Option<u32>,
+    /// Backend used to talk to the Workshop. `fake` keeps items in a local state file instead, for testing.
+    #[arg(long, value_enum, default_value_t, hide = true, global = true)]
+    pub backend: BackendKind,
+    /// State file used by the `fake` backend. Items are only kept in memory if not set.
+    #[arg(long, value_name = "FILE", hide = true, global = true)]
+    pub fake_state: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Command,
 }
@@ -20,6 +37,13 @@ pub struct Cli {
 pub enum Command {
     Create(CreateCommand),
     Update(UpdateCommand),
+    List(ListCommand),
+    Info(InfoCommand),
+    Delete(DeleteCommand),
+    Pull(PullCommand),
+    Migrate(MigrateCommand),
+    LsFiles(LsFilesCommand),
+    Render(RenderCommand),
 }
 
 #[derive(Debug, Clone, clap::Args)]
@@ -28,6 +52,20 @@ pub struct WorkshopItemArgs {
     pub title: Option<String>,
     #[arg(long)]
     pub description: Option<String>,
+    /// File containing the description.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "description",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_file()
+        .map(|it| it.to_path_buf())
+    )]
+    pub description_file: Option<PathBuf>,
+    /// Markup the description is written in. `markdown` is converted to Steam BBCode before uploading.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub description_format: Option<DescriptionFormat>,
     #[arg(
         long = "content",
         value_name = "DIR",
@@ -52,8 +90,62 @@ pub struct WorkshopItemArgs {
         .map(|it| it.to_path_buf())
     )]
     pub preview_path: Option<PathBuf>,
+    /// Upload a downscaled JPEG copy of preview images that are over Steam's size limit or larger than needed, instead
+    /// of failing.
+    #[arg(long)]
+    pub preview_auto_convert: bool,
+    /// Additional preview image to add to the item's gallery. Can be repeated.
+    #[arg(
+        long = "add-preview-image",
+        value_name = "FILE",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_file()
+        .map(|it| it.to_path_buf())
+    )]
+    pub add_preview_images: Vec<PathBuf>,
+    /// YouTube video to add to the item's gallery. Can be repeated.
+    #[arg(long = "add-preview-video", value_name = "VIDEO_ID")]
+    pub add_preview_videos: Vec<String>,
+    /// Additional preview to remove, by its file name, path or YouTube video id. Can be repeated.
+    #[arg(long = "remove-preview", value_name = "PREVIEW")]
+    pub removed_previews: Vec<String>,
     #[arg(short = 'm', long)]
     pub change_log: Option<String>,
+    /// Take the change note from `git`, i.e. the commits since the last upload, or from the topmost release section
+    /// of a Markdown changelog with `changelog:FILE`, relative to the content folder.
+    #[arg(
+        long,
+        value_name = "SOURCE",
+        conflicts_with = "change_log",
+        value_parser = |s: &str| s.parse::<ChangeLogSource>()
+    )]
+    pub change_log_from: Option<ChangeLogSource>,
+    /// Key-value tag to set on the item, replacing any values the key already has. Can be repeated.
+    #[arg(long = "kv", value_name = "KEY=VALUE", value_parser = |s: &str| parse_key_value_tag(s))]
+    pub key_value_tags: Vec<(String, String)>,
+    /// Key whose key-value tags are removed from the item. Can be repeated.
+    #[arg(long = "remove-kv", value_name = "KEY")]
+    pub removed_key_value_tags: Vec<String>,
+    /// Metadata string of the item, as read by the game.
+    #[arg(long, conflicts_with = "metadata_file")]
+    pub metadata: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_file()
+        .map(|it| it.to_path_buf())
+    )]
+    pub metadata_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub content_filter: ContentFilterArgs,
+}
+
+/// Controls which files from the content folder get uploaded.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ContentFilterArgs {
     #[arg(short, long = "glob", value_name = "GLOB")]
     pub globs: Vec<String>,
     #[arg(
@@ -77,6 +169,9 @@ pub struct CreateCommand {
     pub app_id: Option<AppId>,
     #[command(flatten)]
     pub workshop_item: WorkshopItemArgs,
+    /// Validate and stage everything and print what would be published, without creating the item.
+    #[arg(long)]
+    pub dry_run: bool,
 }
 
 /// Update an existing workshop item.
@@ -88,10 +183,162 @@ pub struct UpdateCommand {
     /// Skip updating the workshop item files; only use the content path to access the `workshop.toml` metadata file.
     #[arg(long = "no-content-update")]
     pub no_content_update: bool,
+    /// Upload the content files even if they're the same as in the last upload, according to `workshop.lock`.
+    #[arg(long, conflicts_with = "no_content_update")]
+    pub force_content_update: bool,
+    /// Validate and stage everything and print what would change on the item, without submitting anything.
+    #[arg(long)]
+    pub dry_run: bool,
 }
 
-#[derive(Debug, Clone, Copy, ValueEnum, Default, strum::Display)]
+/// Selects an existing workshop item, either through the `workshop.toml` metadata file in its content folder or by
+/// its id.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ItemSelectArgs {
+    /// Content folder containing the item's `workshop.toml` metadata file.
+    #[arg(
+        long = "content",
+        value_name = "DIR",
+        conflicts_with = "item_id",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_dir()
+        .map(|it| it.to_path_buf())
+    )]
+    pub content_path: Option<PathBuf>,
+    #[arg(long)]
+    pub item_id: Option<u64>,
+    /// Steam AppId. Only needed along with `--item-id`.
+    #[arg(long, value_parser = clap::value_parser!(u32).map(|it| AppId(it)))]
+    pub app_id: Option<AppId>,
+}
+
+/// Show the remote details of a workshop item.
+#[derive(Debug, Clone, Parser)]
+#[command()]
+pub struct InfoCommand {
+    #[command(flatten)]
+    pub item: ItemSelectArgs,
+}
+
+/// Delete a workshop item.
+#[derive(Debug, Clone, Parser)]
+#[command()]
+pub struct DeleteCommand {
+    #[command(flatten)]
+    pub item: ItemSelectArgs,
+    /// Remove the `workshop.toml` metadata file instead of archiving it as `workshop.deleted.toml`.
+    #[arg(long)]
+    pub remove_metadata: bool,
+}
+
+/// Sync the remote title, description, tags and visibility of a workshop item into its `workshop.toml`.
+#[derive(Debug, Clone, Parser)]
+#[command()]
+pub struct PullCommand {
+    #[arg(
+        long = "content",
+        value_name = "DIR",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_dir()
+        .map(|it| it.to_path_buf())
+    )]
+    pub content_path: Option<PathBuf>,
+    /// Write the description to this file, relative to the content folder, instead of `workshop.toml`.
+    #[arg(long, value_name = "FILE")]
+    pub description_file: Option<PathBuf>,
+    /// Also pull the title and description in this language, e.g. `german`. Languages already localized locally are
+    /// always pulled.
+    #[arg(long = "language", value_name = "LANGUAGE")]
+    pub languages: Vec<String>,
+}
+
+/// Upgrade every `workshop.toml` under a directory tree to the current schema version.
+#[derive(Debug, Clone, Parser)]
+#[command()]
+pub struct MigrateCommand {
+    #[arg(
+        value_name = "DIR",
+        default_value = ".",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_dir()
+        .map(|it| it.to_path_buf())
+    )]
+    pub dir: PathBuf,
+}
+
+/// List the files that would be uploaded from a content folder.
+#[derive(Debug, Clone, Parser)]
+#[command()]
+pub struct LsFilesCommand {
+    #[arg(
+        long = "content",
+        value_name = "DIR",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_dir()
+        .map(|it| it.to_path_buf())
+    )]
+    pub content_path: Option<PathBuf>,
+    #[command(flatten)]
+    pub content_filter: ContentFilterArgs,
+    /// List the files and directories that would be skipped instead, along with the rule that excluded each.
+    #[arg(long)]
+    pub excluded: bool,
+}
+
+/// Print an item's description as it would be uploaded, converted to Steam BBCode.
+#[derive(Debug, Clone, Parser)]
+#[command()]
+pub struct RenderCommand {
+    /// Markdown file to convert, instead of the description of the item in the content folder.
+    #[arg(
+        value_name = "FILE",
+        conflicts_with = "content_path",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_file()
+        .map(|it| it.to_path_buf())
+    )]
+    pub file: Option<PathBuf>,
+    #[arg(
+        long = "content",
+        value_name = "DIR",
+        value_parser = clap::value_parser!(ClioPath)
+        .exists()
+        .is_dir()
+        .map(|it| it.to_path_buf())
+    )]
+    pub content_path: Option<PathBuf>,
+}
+
+/// List workshop items published by the logged-in user.
+#[derive(Debug, Clone, Parser)]
+#[command()]
+pub struct ListCommand {
+    /// Steam AppId
+    #[arg(long, value_parser = clap::value_parser!(u32).map(|it| AppId(it)))]
+    pub app_id: Option<AppId>,
+    /// Page of results to start from, 50 items per page.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub page: u32,
+    /// Fetch all pages instead of just one.
+    #[arg(long)]
+    pub all: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, Default)]
+pub enum BackendKind {
+    #[default]
+    Steam,
+    Fake,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, Default, strum::Display, Serialize, Deserialize)]
 #[strum(serialize_all = "PascalCase")]
+#[serde(rename_all = "kebab-case")]
 pub enum PublishedFileVisibility {
     FriendsOnly,
     #[default]
@@ -100,6 +347,17 @@ pub enum PublishedFileVisibility {
     Unlisted,
 }
 
+/// Markup of an item's description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DescriptionFormat {
+    /// Steam BBCode, uploaded as is.
+    #[default]
+    Bbcode,
+    /// Converted to Steam BBCode before uploading.
+    Markdown,
+}
+
 // pain
 impl Into<PublishedFileVisibility> for steamworks::PublishedFileVisibility {
     fn into(self) -> PublishedFileVisibility {
@@ -1,18 +1,21 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     env,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use better_default::Default;
 
-use color_eyre::eyre::{self, bail, ContextCompat};
+use color_eyre::eyre::{self, bail, Context, ContextCompat};
 use fs_err::PathExt;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::{info, warn};
 
 use crate::{
+    cli::{DescriptionFormat, PublishedFileVisibility},
     defines::APP_CONFIG_PATH,
+    migration::{read_workshop_metadata, WORKSHOP_SCHEMA_VERSION},
     workshop::{AppId, Tag},
 };
 
@@ -101,6 +104,81 @@ pub struct AppConfig {
     #[default(true)]
     pub open_item_page_on_complete: bool,
     pub valid_tags: HashMap<AppId, Vec<Tag>>,
+    pub timeouts: Timeouts,
+    pub retry: RetryPolicy,
+    /// Applies to items that don't set their own in `workshop.toml`.
+    pub git: GitGuards,
+}
+
+/// Checks on the content folder's git repository before an item is updated. All disabled by default.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GitGuards {
+    /// Refuse to update when there are uncommitted changes, other than to the files written by this tool
+    /// (`workshop.lock`, and the backups and archive of `workshop.toml`).
+    pub require_clean: bool,
+    /// Refuse to update unless HEAD is on one of these branches. Any branch is allowed when empty.
+    pub allowed_branches: Vec<String>,
+    /// Refuse to update unless HEAD is tagged.
+    pub require_tag: bool,
+    /// Tag HEAD as `workshop-<item_id>-<n>` after every successful content upload.
+    pub tag_uploads: bool,
+}
+
+impl GitGuards {
+    pub fn is_enabled(&self) -> bool {
+        self.require_clean
+            || !self.allowed_branches.is_empty()
+            || self.require_tag
+            || self.tag_uploads
+    }
+}
+
+/// Retrying of Steam operations that failed for transient reasons, such as Steam being busy or rate limiting.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Number of retries after the initial attempt. `0` disables retrying.
+    #[default(3)]
+    pub retries: u32,
+    /// Backoff before the first retry, doubled after every attempt.
+    #[default(5)]
+    pub initial_backoff_secs: u64,
+    #[default(60)]
+    pub max_backoff_secs: u64,
+}
+
+/// How long to wait on Steam for each kind of operation, in seconds. `0` disables the timeout.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timeouts {
+    #[default(60)]
+    pub create_secs: u64,
+    #[default(30)]
+    pub query_secs: u64,
+    /// Counts from the last time the upload made progress, rather than from the start.
+    #[default(300)]
+    pub submit_secs: u64,
+    #[default(60)]
+    pub delete_secs: u64,
+}
+
+impl Timeouts {
+    fn duration(secs: u64) -> Option<Duration> {
+        (secs != 0).then(|| Duration::from_secs(secs))
+    }
+    pub fn create(&self) -> Option<Duration> {
+        Self::duration(self.create_secs)
+    }
+    pub fn query(&self) -> Option<Duration> {
+        Self::duration(self.query_secs)
+    }
+    pub fn submit(&self) -> Option<Duration> {
+        Self::duration(self.submit_secs)
+    }
+    pub fn delete(&self) -> Option<Duration> {
+        Self::duration(self.delete_secs)
+    }
 }
 
 impl Config for AppConfig {}
@@ -113,14 +191,101 @@ impl ConfigWithPathExt for AppConfig {
 /// It should be stored in the workshop item content directory.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct WorkshopItemConfig {
+    #[default(WORKSHOP_SCHEMA_VERSION)]
+    #[serde(default)]
+    pub schema_version: u32,
     pub app_id: u32,
     pub item_id: u64,
     /// Tags need to be stored in the metadata file, as Steam doesn’t retain them ifno tags are provided to Steamworks
     /// during an item update.
     pub tags: Vec<Tag>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// File containing the description, relative to the content directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description_file: Option<PathBuf>,
+    /// Markup the description is written in. Markdown is converted to Steam BBCode before uploading.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description_format: Option<DescriptionFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<PublishedFileVisibility>,
+    /// Preview image, relative to the content directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<PathBuf>,
+    /// Shown in the item's gallery after the primary preview, in this order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previews: Vec<PreviewConfig>,
+    /// Metadata string of the item, as read by the game.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    /// File containing the metadata string, relative to the content directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_file: Option<PathBuf>,
+    /// Each key replaces whatever values it already has on the item.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub key_value_tags: BTreeMap<String, String>,
+    /// Replaces the git checks of the app config for this item.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitGuards>,
+    /// Wraps change notes generated from the content changes, `{changes}` being replaced with the list of changed
+    /// files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_note_template: Option<String>,
+    /// Command supplying the metadata string, key-value tags and change note at upload time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generator: Option<GeneratorConfig>,
+    /// Title and description in other languages, keyed by Steam API language code, e.g. `german`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub localization: BTreeMap<String, LocalizationConfig>,
+}
+
+/// An additional preview of an item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewConfig {
+    /// Image file, relative to the content directory.
+    Image(PathBuf),
+    /// YouTube video id.
+    #[serde(rename = "youtube")]
+    YouTube(String),
+}
+
+/// Title and description of an item in a language other than its default one.
+///
+/// Either inlined in `workshop.toml`, or stored as `workshop-localization/<language>.toml` in the content directory.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LocalizationConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// File containing the description, relative to the content directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description_file: Option<PathBuf>,
+}
+
+impl Config for LocalizationConfig {}
+
+/// A command run right before an item update is submitted, printing a JSON object with any of `metadata`,
+/// `key_value_tags` and `change_note` on stdout.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GeneratorConfig {
+    /// Program followed by its arguments. The program can be a script relative to the content directory.
+    pub command: Vec<String>,
 }
 
-impl Config for WorkshopItemConfig {}
+impl Config for WorkshopItemConfig {
+    /// Upgrades outdated files to the current schema version in memory, without writing them.
+    fn try_load_path(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        if !path.as_ref().fs_err_canonicalize()?.is_file() {
+            bail!("{:?} is not a file", path.as_ref());
+        }
+        toml::from_str(&read_workshop_metadata(&path)?)
+            .with_context(|| format!("Failed to parse {:?}", path.as_ref()))
+    }
+}
 
 #[allow(unused)]
 pub trait Config
@@ -20,3 +20,10 @@ pub static APP_CONFIG_PATH: LazyLock<PathBuf> =
 pub static APP_LOG_DIR: LazyLock<PathBuf> = LazyLock::new(|| APP_CACHE_DIR.join("log"));
 
 pub const WORKSHOP_METADATA_FILENAME: &str = "workshop.toml";
+/// Metadata files of deleted items are renamed to this, instead of being removed outright.
+pub const WORKSHOP_METADATA_ARCHIVE_FILENAME: &str = "workshop.deleted.toml";
+/// Manifest of the files in an item's last successful content upload, next to its metadata file.
+pub const WORKSHOP_MANIFEST_FILENAME: &str = "workshop.lock";
+/// Directory in the content folder holding `<language>.toml` localization files. It's never uploaded, and named after
+/// this tool so that it doesn't clash with the item's own files.
+pub const WORKSHOP_LOCALIZATION_DIR: &str = "workshop-localization";
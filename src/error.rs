@@ -0,0 +1,181 @@
+use color_eyre::{eyre, owo_colors::OwoColorize};
+
+use crate::{
+    ext::{CallbackError, INTERRUPTED_EXIT_CODE},
+    output::{self, Event},
+};
+
+/// Exit code used in `--no-prompt` mode when the item was published, but stays hidden until the user accepts the
+/// Workshop legal agreement.
+pub const LEGAL_AGREEMENT_EXIT_CODE: u8 = 88;
+
+/// Broad causes of failures, each exiting with its own code so that scripts can tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// Not logged in, or no connection to Steam.
+    Offline,
+    /// Missing permissions, licenses or agreements.
+    AccessDenied,
+    /// Banned or otherwise restricted from the Workshop.
+    Restricted,
+    /// Quotas or size limits exceeded.
+    LimitExceeded,
+    /// The item or one of its files couldn't be found.
+    NotFound,
+    /// Conflicts with another request in progress.
+    Conflict,
+    /// Steam rejected one of the values submitted.
+    InvalidInput,
+    /// Likely to succeed if tried again later.
+    Transient,
+}
+
+impl ErrorCategory {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCategory::Offline => 80,
+            ErrorCategory::AccessDenied => 81,
+            ErrorCategory::Restricted => 82,
+            ErrorCategory::LimitExceeded => 83,
+            ErrorCategory::NotFound => 84,
+            ErrorCategory::Conflict => 85,
+            ErrorCategory::InvalidInput => 86,
+            ErrorCategory::Transient => 87,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Explanation {
+    pub category: ErrorCategory,
+    pub reason: &'static str,
+    pub hint: &'static str,
+}
+
+/// Explains the common results returned by UGC calls.
+///
+/// https://partner.steamgames.com/doc/api/ISteamUGC#SubmitItemUpdateResult_t
+pub fn explain_steam_error(err: &steamworks::SteamError) -> Option<Explanation> {
+    use steamworks::SteamError;
+
+    let (category, reason, hint) = match err {
+        SteamError::NotLoggedOn | SteamError::NoConnection => (
+            ErrorCategory::Offline,
+            "Not logged in to Steam, or Steam is offline",
+            "Make sure Steam is running, online and logged in to the account that owns the game",
+        ),
+        SteamError::AccessDenied => (
+            ErrorCategory::AccessDenied,
+            "Steam denied access to the item",
+            "Make sure you own the game, that you're the owner of the item, and that you have accepted the Workshop legal agreement",
+        ),
+        SteamError::InsufficientPrivilege => (
+            ErrorCategory::AccessDenied,
+            "Your account is currently restricted from uploading content",
+            "This is usually due to a hub ban, account lock or community ban. Limited accounts also can't publish to the Workshop",
+        ),
+        SteamError::Banned => (
+            ErrorCategory::Restricted,
+            "Your account is banned from the Workshop of this game",
+            "Check the account's standing in the game's Workshop on the Steam website",
+        ),
+        SteamError::LimitExceeded => (
+            ErrorCategory::LimitExceeded,
+            "The preview image is too large, or it exceeds the free space of your Steam Cloud",
+            "Use a preview image under 1 MB, or free up space in your Steam Cloud",
+        ),
+        SteamError::FileNotFound => (
+            ErrorCategory::NotFound,
+            "The workshop item, its content folder or its preview image couldn't be found",
+            "Check that `item_id` in `workshop.toml` refers to an existing item, and that the content folder and preview are readable",
+        ),
+        SteamError::DuplicateRequest => (
+            ErrorCategory::Conflict,
+            "Another update of this item is already in progress",
+            "Wait for the pending update to finish before trying again",
+        ),
+        SteamError::LockingFailed => (
+            ErrorCategory::Conflict,
+            "Steam failed to acquire a lock on the item",
+            "Another update might be in progress. Try again in a moment",
+        ),
+        SteamError::InvalidParameter => (
+            ErrorCategory::InvalidInput,
+            "Steam rejected one of the submitted values",
+            "Titles are limited to 128 characters, descriptions to 8000 and change notes to 8000. Also check the tags and visibility",
+        ),
+        SteamError::Busy
+        | SteamError::Timeout
+        | SteamError::ServiceUnavailable
+        | SteamError::RateLimitExceeded
+        | SteamError::IOFailure
+        | SteamError::RemoteDisconnect => (
+            ErrorCategory::Transient,
+            "Steam is busy, unavailable or rate limiting requests",
+            "Try again later, or increase the number of retries with `--retries`",
+        ),
+        _ => return None,
+    };
+
+    Some(Explanation {
+        category,
+        reason,
+        hint,
+    })
+}
+
+/// Exits with an explanation and a distinct exit code for errors that are known to be caused by Steam, or by the user
+/// cancelling. Returns for anything else.
+///
+/// An error event is emitted in either case.
+pub fn exit_on_known_error(report: &eyre::Report) {
+    if let Some(err) = report
+        .chain()
+        .find_map(|it| it.downcast_ref::<CallbackError>())
+    {
+        match err {
+            CallbackError::Cancelled => {
+                output::emit(Event::Error {
+                    message: err.to_string(),
+                    code: INTERRUPTED_EXIT_CODE as u8,
+                    hint: None,
+                });
+                eprintln!("{}", "[!] Cancelled.".red());
+                quit::with_code(INTERRUPTED_EXIT_CODE as u8);
+            }
+            CallbackError::TimedOut(_) => {
+                let hint = "Steam might have been closed or lost its connection. Timeouts can be adjusted in the config.";
+                output::emit(Event::Error {
+                    message: err.to_string(),
+                    code: ErrorCategory::Transient.exit_code(),
+                    hint: Some(hint.to_owned()),
+                });
+                eprintln!("{}", format!("[!] {err}").red());
+                eprintln!("    {}", hint.dimmed());
+                quit::with_code(ErrorCategory::Transient.exit_code());
+            }
+            CallbackError::Disconnected => {}
+        }
+    }
+
+    if let Some(explanation) = report
+        .chain()
+        .find_map(|it| it.downcast_ref::<steamworks::SteamError>())
+        .and_then(explain_steam_error)
+    {
+        output::emit(Event::Error {
+            message: format!("{report}: {}", explanation.reason),
+            code: explanation.category.exit_code(),
+            hint: Some(explanation.hint.to_owned()),
+        });
+        eprintln!("{}", format!("[!] {report}: {}", explanation.reason).red());
+        eprintln!("    {}", explanation.hint.dimmed());
+        quit::with_code(explanation.category.exit_code());
+    }
+
+    output::emit(Event::Error {
+        message: format!("{report:#}"),
+        code: 1,
+        hint: None,
+    });
+}
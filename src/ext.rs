@@ -1,34 +1,130 @@
-use std::sync::mpsc;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
 
-use color_eyre::eyre::{self, bail};
-use tracing::error;
+use color_eyre::eyre;
+use tracing::{error, warn};
 
 pub type SteamworksClient = steamworks::Client<steamworks::ClientManager>;
 pub type SteamworksSingleClient = steamworks::SingleClient<steamworks::ClientManager>;
 
-#[macro_export]
-macro_rules! run_callbacks_blocking {
-    ($single:ident, $rx:ident) => {{
-        use ::std::sync::mpsc;
-        let out;
-        loop {
-            match $rx.try_recv() {
-                Err(mpsc::TryRecvError::Empty) => {
-                    $single.run_callbacks();
-                    ::std::thread::sleep(::std::time::Duration::from_millis(100));
-                }
-                Err(err @ mpsc::TryRecvError::Disconnected) => {
-                    bail!(err);
-                }
-                Ok(result) => {
-                    out = result;
-                    break;
-                }
+/// Whether something is currently blocked in [`poll_blocking`], in which case Ctrl-C cancels the wait
+/// instead of exiting right away.
+static WAITING_FOR_CALLBACK: AtomicBool = AtomicBool::new(false);
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Exit code used when the process is interrupted with Ctrl-C.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+pub fn install_cancel_handler() -> eyre::Result<()> {
+    ctrlc::set_handler(|| {
+        if WAITING_FOR_CALLBACK.load(Ordering::SeqCst) {
+            warn!("Cancellation requested");
+            CANCEL_REQUESTED.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    })?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallbackError {
+    /// Steam didn't respond within the timeout.
+    TimedOut(Duration),
+    /// The wait was cancelled with Ctrl-C.
+    Cancelled,
+    /// The callback was dropped without ever sending a result.
+    Disconnected,
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallbackError::TimedOut(timeout) => write!(
+                f,
+                "Timed out after {}s waiting for a response from Steam",
+                timeout.as_secs()
+            ),
+            CallbackError::Cancelled => {
+                write!(f, "Cancelled while waiting for a response from Steam")
+            }
+            CallbackError::Disconnected => {
+                write!(f, "Steam callback was dropped without a response")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CallbackError {}
+
+/// Runs Steam callbacks until a result arrives on `rx`.
+///
+/// `on_tick` is called on every poll, and should return `true` whenever the operation made progress, which restarts
+/// the `timeout`. Operations that don't report progress therefore time out after `timeout` in total. No timeout is
+/// applied if it's `None`.
+pub fn run_callbacks_blocking<T>(
+    single: &SteamworksSingleClient,
+    rx: &mpsc::Receiver<T>,
+    timeout: Option<Duration>,
+    mut on_tick: impl FnMut() -> bool,
+) -> Result<T, CallbackError> {
+    poll_blocking(timeout, || {
+        match rx.try_recv() {
+            Ok(result) => return Ok((Some(result), false)),
+            Err(mpsc::TryRecvError::Disconnected) => return Err(CallbackError::Disconnected),
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
+
+        single.run_callbacks();
+        Ok((None, on_tick()))
+    })
+}
+
+/// Calls `poll` until it returns a result, which it does along with whether the operation made progress since the
+/// last call. Ctrl-C cancels the wait, and the `timeout` is handled the same as in [`run_callbacks_blocking`].
+pub fn poll_blocking<T>(
+    timeout: Option<Duration>,
+    mut poll: impl FnMut() -> Result<(Option<T>, bool), CallbackError>,
+) -> Result<T, CallbackError> {
+    struct WaitGuard;
+    impl Drop for WaitGuard {
+        fn drop(&mut self) {
+            WAITING_FOR_CALLBACK.store(false, Ordering::SeqCst);
+        }
+    }
+
+    CANCEL_REQUESTED.store(false, Ordering::SeqCst);
+    WAITING_FOR_CALLBACK.store(true, Ordering::SeqCst);
+    let _guard = WaitGuard;
+
+    let mut last_progress = Instant::now();
+    loop {
+        if CANCEL_REQUESTED.swap(false, Ordering::SeqCst) {
+            return Err(CallbackError::Cancelled);
+        }
+
+        let (result, made_progress) = poll()?;
+        if let Some(result) = result {
+            return Ok(result);
+        }
+        if made_progress {
+            last_progress = Instant::now();
+        }
+
+        if let Some(timeout) = timeout {
+            if last_progress.elapsed() >= timeout {
+                return Err(CallbackError::TimedOut(timeout));
             }
         }
 
-        out
-    }};
+        std::thread::sleep(Duration::from_millis(100));
+    }
 }
 
 pub trait UGCBlockingExt {
@@ -37,7 +133,14 @@ pub trait UGCBlockingExt {
         single: &SteamworksSingleClient,
         app_id: steamworks::AppId,
         file_type: steamworks::FileType,
+        timeout: Option<Duration>,
     ) -> eyre::Result<(steamworks::PublishedFileId, bool)>;
+    fn delete_item_blocking(
+        &self,
+        single: &SteamworksSingleClient,
+        file_id: steamworks::PublishedFileId,
+        timeout: Option<Duration>,
+    ) -> eyre::Result<()>;
 }
 
 impl<Manager> UGCBlockingExt for steamworks::UGC<Manager> {
@@ -46,6 +149,7 @@ impl<Manager> UGCBlockingExt for steamworks::UGC<Manager> {
         single: &SteamworksSingleClient,
         app_id: steamworks::AppId,
         file_type: steamworks::FileType,
+        timeout: Option<Duration>,
     ) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
         let (tx, rx) = mpsc::channel();
 
@@ -55,15 +159,35 @@ impl<Manager> UGCBlockingExt for steamworks::UGC<Manager> {
 
         // We love single.run_callbacks()!
         // Best API in the world
-        Ok(run_callbacks_blocking!(single, rx)?)
+        Ok(run_callbacks_blocking(single, &rx, timeout, || false)??)
+    }
+
+    fn delete_item_blocking(
+        &self,
+        single: &SteamworksSingleClient,
+        file_id: steamworks::PublishedFileId,
+        timeout: Option<Duration>,
+    ) -> eyre::Result<()> {
+        let (tx, rx) = mpsc::channel();
+
+        self.delete_item(file_id, move |result| {
+            _ = tx.send(result).inspect_err(|e| error!(%e));
+        });
+
+        Ok(run_callbacks_blocking(single, &rx, timeout, || false)??)
     }
 }
 
 pub trait UpdateHandleBlockingExt {
+    /// `on_progress` receives the update watch handle's `(status, bytes processed, bytes total)` on every poll.
+    ///
+    /// `timeout` counts from the last time the upload made progress.
     fn submit_blocking(
         self,
         single: &SteamworksSingleClient,
         change_note: Option<&str>,
+        timeout: Option<Duration>,
+        on_progress: impl FnMut(steamworks::UpdateStatus, u64, u64),
     ) -> eyre::Result<(steamworks::PublishedFileId, bool)>;
 }
 
@@ -72,13 +196,24 @@ impl<Manager> UpdateHandleBlockingExt for steamworks::UpdateHandle<Manager> {
         self,
         single: &SteamworksSingleClient,
         change_note: Option<&str>,
+        timeout: Option<Duration>,
+        mut on_progress: impl FnMut(steamworks::UpdateStatus, u64, u64),
     ) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
         let (tx, rx) = mpsc::channel();
 
-        self.submit(change_note, move |result| {
+        let watch_handle = self.submit(change_note, move |result| {
             _ = tx.send(result).inspect_err(|e| error!(%e));
         });
 
-        Ok(run_callbacks_blocking!(single, rx)?)
+        let mut last_progress = None;
+        Ok(run_callbacks_blocking(single, &rx, timeout, || {
+            let (status, processed, total) = watch_handle.progress();
+            on_progress(status, processed, total);
+
+            let progress = Some((status as u32, processed));
+            let made_progress = progress != last_progress;
+            last_progress = progress;
+            made_progress
+        })??)
     }
 }
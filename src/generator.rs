@@ -0,0 +1,157 @@
+use std::{
+    collections::BTreeMap,
+    fs::Permissions,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use color_eyre::eyre::{self, bail, Context, ContextCompat};
+use fs_err::PathExt;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{
+    backend::ItemUpdate,
+    changelog::truncate_change_note,
+    cli::WorkshopItemArgs,
+    config::GeneratorConfig,
+    workshop::{check_key_value_tag, check_metadata},
+};
+
+/// What a generator prints on stdout, as a JSON object. Every field is optional.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneratedMetadata {
+    pub metadata: Option<String>,
+    pub key_value_tags: BTreeMap<String, String>,
+    pub change_note: Option<String>,
+}
+
+/// Sets the read-only flag of `path` and every file and directory under it, symlinks aside. Returns the previous
+/// permissions, for [`restore_permissions`].
+///
+/// Read-only directories keep entries from being added, removed or renamed, except on Windows where the flag is
+/// ignored for directories.
+fn make_readonly(path: &Path) -> eyre::Result<Vec<(PathBuf, Permissions)>> {
+    let mut previous = vec![];
+    for entry in ignore::WalkBuilder::new(path)
+        .standard_filters(false)
+        .build()
+    {
+        let entry = entry?;
+        if entry
+            .file_type()
+            .is_some_and(|it| it.is_file() || it.is_dir())
+        {
+            let permissions = fs_err::metadata(entry.path())?.permissions();
+            let mut readonly = permissions.clone();
+            readonly.set_readonly(true);
+            fs_err::set_permissions(entry.path(), readonly)?;
+            previous.push((entry.into_path(), permissions));
+        }
+    }
+    Ok(previous)
+}
+
+/// Directories last, so that they're still read-only while their entries are restored.
+fn restore_permissions(previous: Vec<(PathBuf, Permissions)>) -> eyre::Result<()> {
+    for (path, permissions) in previous.into_iter().rev() {
+        fs_err::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
+/// Runs the generator with the content and staging directories as its arguments, the latter being read-only for the
+/// duration. The content directory is passed in place of the staging one if the content isn't being updated.
+///
+/// A program relative to the content directory is run from there, anything else is looked up in `PATH`.
+pub fn run_generator(
+    generator: &GeneratorConfig,
+    content_path: &Path,
+    staging_path: Option<&Path>,
+    app_id: u32,
+    item_id: u64,
+) -> eyre::Result<GeneratedMetadata> {
+    // The generator runs from the content directory, so relative paths would no longer point to the right place
+    let content_path = &content_path.fs_err_canonicalize()?;
+    let staging_path = staging_path
+        .map(|it| it.fs_err_canonicalize())
+        .transpose()?;
+    let staging_path = staging_path.as_deref();
+    let (program, args) = generator
+        .command
+        .split_first()
+        .context("Generator command is empty")?;
+    let program = match content_path.join(program) {
+        it if it.is_file() => it.into_os_string(),
+        _ => program.into(),
+    };
+
+    info!(?program, ?args, "Running metadata generator");
+
+    let previous_permissions = staging_path.map(make_readonly).transpose()?;
+    let output = Command::new(&program)
+        .args(args)
+        .arg(content_path)
+        .arg(staging_path.unwrap_or(content_path))
+        .current_dir(content_path)
+        .env("WORKSHOP_APP_ID", app_id.to_string())
+        .env("WORKSHOP_ITEM_ID", item_id.to_string())
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run generator {:?}", program));
+    if let Some(previous_permissions) = previous_permissions {
+        restore_permissions(previous_permissions)?;
+    }
+    let output = output?;
+
+    if !output.status.success() {
+        bail!("Generator {:?} failed with {}", program, output.status);
+    }
+
+    let mut generated: GeneratedMetadata = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Generator {:?} didn't print a valid JSON object", program))?;
+
+    if let Some(metadata) = &generated.metadata {
+        check_metadata(metadata)?;
+    }
+    for (key, value) in &generated.key_value_tags {
+        check_key_value_tag(key, value)?;
+    }
+    generated.change_note = generated.change_note.map(truncate_change_note);
+
+    Ok(generated)
+}
+
+/// Fills `update` with the generated values, which take precedence over `workshop.toml` but not over the ones in
+/// `cli_args`.
+pub fn apply_generated_metadata(
+    update: &mut ItemUpdate,
+    generated: GeneratedMetadata,
+    cli_args: &WorkshopItemArgs,
+) {
+    if cli_args.metadata.is_none()
+        && cli_args.metadata_file.is_none()
+        && generated.metadata.is_some()
+    {
+        update.metadata = generated.metadata;
+    }
+    for (key, value) in generated.key_value_tags {
+        if cli_args.key_value_tags.iter().any(|(it, _)| *it == key)
+            || cli_args.removed_key_value_tags.contains(&key)
+        {
+            warn!(
+                key,
+                "Ignoring generated key-value tag overridden by cli args"
+            );
+            continue;
+        }
+        update.key_value_tags.insert(key, value);
+    }
+    if update.change_note.as_deref().unwrap_or_default().is_empty()
+        && generated.change_note.is_some()
+    {
+        update.change_note = generated.change_note;
+    }
+}
@@ -0,0 +1,169 @@
+use std::{path::Path, process::Command};
+
+use color_eyre::eyre::{self, bail, Context};
+use tracing::{debug, info};
+
+use crate::{
+    config::GitGuards,
+    defines::{WORKSHOP_MANIFEST_FILENAME, WORKSHOP_METADATA_ARCHIVE_FILENAME},
+    migration::WORKSHOP_METADATA_BACKUP_GLOB,
+};
+
+/// Runs `git` in `repo`, returning its trimmed stdout.
+fn git(repo: &Path, args: &[&str]) -> eyre::Result<String> {
+    debug!(?repo, ?args, "Running git");
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_owned())
+}
+
+/// Whether `path` is inside a git working tree. Also false if git isn't installed.
+pub fn is_repo(path: &Path) -> bool {
+    git(path, &["rev-parse", "--is-inside-work-tree"]).is_ok_and(|it| it == "true")
+}
+
+/// The commit checked out in the repo containing `path`, if it's in one.
+pub fn head_commit(path: &Path) -> eyre::Result<Option<String>> {
+    if !is_repo(path) {
+        return Ok(None);
+    }
+    Ok(Some(git(path, &["rev-parse", "HEAD"])?))
+}
+
+/// Subjects of the commits after `since` up to HEAD, oldest first. Only the last `max_count` commits are returned
+/// if `since` isn't given.
+pub fn commit_subjects_since(
+    path: &Path,
+    since: Option<&str>,
+    max_count: usize,
+) -> eyre::Result<Vec<String>> {
+    let range = match since {
+        Some(since) => format!("{since}..HEAD"),
+        None => "HEAD".to_owned(),
+    };
+    let max_count = format!("--max-count={max_count}");
+    let mut args = vec!["log", "--format=%s", &range];
+    if since.is_none() {
+        args.push(&max_count);
+    }
+
+    let mut subjects = git(path, &args)?
+        .lines()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    subjects.reverse();
+    Ok(subjects)
+}
+
+/// Whether the working tree of the repo containing `path` has uncommitted changes, including untracked files.
+///
+/// Files matching the glob patterns in `ignored`, relative to `path`, are left out.
+pub fn is_dirty(path: &Path, ignored: &[&str]) -> eyre::Result<bool> {
+    let excludes = ignored
+        .iter()
+        .map(|it| format!(":(exclude){it}"))
+        .collect::<Vec<_>>();
+    let mut args = vec!["status", "--porcelain", "--", ":/"];
+    args.extend(excludes.iter().map(String::as_str));
+    Ok(!git(path, &args)?.is_empty())
+}
+
+/// The branch checked out in the repo containing `path`, or `None` if HEAD is detached.
+pub fn current_branch(path: &Path) -> eyre::Result<Option<String>> {
+    let branch = git(path, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    Ok((branch != "HEAD").then_some(branch))
+}
+
+/// Tags pointing at HEAD in the repo containing `path`.
+pub fn tags_at_head(path: &Path) -> eyre::Result<Vec<String>> {
+    Ok(git(path, &["tag", "--points-at", "HEAD"])?
+        .lines()
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Tags matching the glob `pattern` in the repo containing `path`.
+pub fn tags_matching(path: &Path, pattern: &str) -> eyre::Result<Vec<String>> {
+    Ok(git(path, &["tag", "--list", pattern])?
+        .lines()
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Creates a lightweight tag `name` at HEAD in the repo containing `path`.
+pub fn create_tag(path: &Path, name: &str) -> eyre::Result<()> {
+    git(path, &["tag", name])?;
+    Ok(())
+}
+
+/// Refuses to go on if the repo containing `content_path` doesn't pass the enabled `guards`.
+pub fn check_guards(guards: &GitGuards, content_path: &Path) -> eyre::Result<()> {
+    if !guards.is_enabled() {
+        return Ok(());
+    }
+    if !is_repo(content_path) {
+        bail!(
+            "Git checks are enabled, but content folder {:?} is not in a git repository",
+            content_path
+        );
+    }
+
+    // Written by the tool itself, after every upload, on migration and on deletion. `workshop.toml` holds the metadata
+    // that's about to be published, so it's checked like any other file.
+    if guards.require_clean
+        && is_dirty(
+            content_path,
+            &[
+                WORKSHOP_MANIFEST_FILENAME,
+                WORKSHOP_METADATA_BACKUP_GLOB,
+                WORKSHOP_METADATA_ARCHIVE_FILENAME,
+            ],
+        )?
+    {
+        bail!("Git working tree has uncommitted changes. Commit or stash them before publishing");
+    }
+    if !guards.allowed_branches.is_empty() {
+        match current_branch(content_path)? {
+            Some(branch) if guards.allowed_branches.contains(&branch) => {}
+            Some(branch) => bail!(
+                "Publishing from branch `{branch}` is not allowed, only from: {}",
+                guards.allowed_branches.join(", ")
+            ),
+            None => bail!("HEAD is detached, while publishing is only allowed from a branch"),
+        }
+    }
+    if guards.require_tag && tags_at_head(content_path)?.is_empty() {
+        bail!("HEAD is not tagged. Tag the commit to publish first");
+    }
+
+    Ok(())
+}
+
+/// Tags HEAD as the `n`th upload of the item, as `workshop-<item_id>-<n>`. Returns the tag.
+pub fn tag_upload(content_path: &Path, item_id: u64) -> eyre::Result<String> {
+    let prefix = format!("workshop-{item_id}-");
+    let n = tags_matching(content_path, &format!("{prefix}*"))?
+        .iter()
+        .filter_map(|it| it.strip_prefix(&prefix)?.parse::<u32>().ok())
+        .max()
+        .unwrap_or_default()
+        + 1;
+
+    let tag = format!("{prefix}{n}");
+    create_tag(content_path, &tag)?;
+    info!(tag, "Tagged upload");
+    Ok(tag)
+}
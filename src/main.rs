@@ -1,25 +1,46 @@
+mod backend;
+mod bbcode;
+mod changelog;
 mod cli;
 mod config;
 mod defines;
+mod error;
 mod ext;
+mod generator;
+mod git;
+mod manifest;
+mod migration;
+mod output;
+mod preview;
+mod progress;
 mod workshop;
 
-use std::{path::PathBuf, str::FromStr, sync::mpsc};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+use backend::{BackendOptions, ItemUpdate, WorkshopBackend, ITEMS_PER_PAGE};
 use clap::Parser;
-use cli::{Cli, PublishedFileVisibility, WorkshopItemArgs};
+use cli::{Cli, ItemSelectArgs, PublishedFileVisibility, WorkshopItemArgs};
 use color_eyre::{
     eyre::{self, bail, ContextCompat},
     owo_colors::OwoColorize,
 };
-use config::{AppConfig, Config, ConfigWithPath, WorkshopItemConfig};
+use config::{AppConfig, Config, ConfigWithPath, RetryPolicy, WorkshopItemConfig};
 use defines::{APP_LOG_DIR, WORKSHOP_METADATA_FILENAME};
-use ext::UpdateHandleBlockingExt;
+use error::LEGAL_AGREEMENT_EXIT_CODE;
 use itertools::Itertools;
-use tracing::{error, info};
+use manifest::ContentManifest;
+use output::Event;
+use progress::UploadProgressReporter;
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use tracing_utils::{format::SourceFormatter, writer::RotatingFileWriter};
-use workshop::{check_tags_are_predefined, is_valid_preview_type, open_workshop_page, Tag};
+use workshop::{
+    check_metadata, check_tags_are_predefined, format_size, format_timestamp,
+    is_valid_preview_type, open_workshop_page, AppId, StagedFile, Tag,
+};
 
 #[allow(unused)]
 macro_rules! exit_on_err {
@@ -62,14 +83,30 @@ fn main() -> eyre::Result<()> {
         )
         .init();
 
-    run().inspect_err(|e| error!("{e}"))?;
+    let result = run().inspect_err(|e| error!("{e}"));
+    if let Err(report) = &result {
+        error::exit_on_known_error(report);
+    }
+    result?;
 
     Ok(())
 }
 
 fn run() -> eyre::Result<()> {
     let cli = Cli::parse();
+    output::init(cli.output);
     let config = ConfigWithPath::<AppConfig>::load()?;
+    let backend_options = BackendOptions {
+        kind: cli.backend,
+        fake_state_path: cli.fake_state.clone(),
+        timeouts: config.inner.timeouts.clone(),
+        retry: RetryPolicy {
+            retries: cli.retries.unwrap_or(config.inner.retry.retries),
+            ..config.inner.retry.clone()
+        },
+    };
+
+    ext::install_cancel_handler()?;
 
     fn inquire_content_path() -> eyre::Result<PathBuf> {
         Ok(PathBuf::from_str(&exit_on_none!(inquire::Text::new(
@@ -89,6 +126,71 @@ fn run() -> eyre::Result<()> {
         .prompt_skippable()?))?)
     }
 
+    fn app_id_or_prompt(app_id: Option<AppId>, no_prompt: bool) -> eyre::Result<AppId> {
+        app_id
+            .map(|it| Ok(it))
+            .unwrap_or_else(|| -> eyre::Result<_> {
+                if no_prompt {
+                    bail!("AppId is required");
+                } else {
+                    Ok(
+                        exit_on_none!(inquire::CustomType::<u32>::new("AppId").prompt_skippable()?)
+                            .into(),
+                    )
+                }
+            })
+    }
+
+    fn content_path_or_prompt(
+        content_path: Option<PathBuf>,
+        no_prompt: bool,
+    ) -> eyre::Result<PathBuf> {
+        content_path.map(|it| Ok(it)).unwrap_or_else(|| {
+            if no_prompt {
+                bail!("Path to Content Folder is required")
+            } else {
+                inquire_content_path()
+            }
+        })
+    }
+
+    /// Exits with a usage error if `content_path` has no metadata file.
+    fn load_item_config(content_path: &Path) -> eyre::Result<WorkshopItemConfig> {
+        let metadata_path = content_path.join(WORKSHOP_METADATA_FILENAME);
+        if !metadata_path.is_file() {
+            eprintln!(
+                "Missing metadata file `{}` from {:?}",
+                WORKSHOP_METADATA_FILENAME, content_path
+            );
+            quit::with_code(exitcode::USAGE as u8);
+        }
+
+        WorkshopItemConfig::try_load_path(metadata_path)
+    }
+
+    /// Returns the AppId and item id of the selected item, along with its content path if it was selected through
+    /// its metadata file.
+    fn resolve_item(
+        item: &ItemSelectArgs,
+        no_prompt: bool,
+    ) -> eyre::Result<(AppId, u64, Option<PathBuf>)> {
+        if let Some(item_id) = item.item_id {
+            return Ok((app_id_or_prompt(item.app_id, no_prompt)?, item_id, None));
+        }
+        if no_prompt && item.content_path.is_none() {
+            bail!("Either a Content Folder or an item id is required")
+        }
+
+        let content_path = content_path_or_prompt(item.content_path.clone(), no_prompt)?;
+        let workshop_item_cfg = load_item_config(&content_path)?;
+
+        Ok((
+            workshop_item_cfg.app_id.into(),
+            workshop_item_cfg.item_id,
+            Some(content_path),
+        ))
+    }
+
     fn inquire_preview_path() -> eyre::Result<Option<String>> {
         Ok(inquire::Text::new("Preview Image")
             .with_help_message("Suggested formats include JPG, PNG and GIF")
@@ -104,27 +206,145 @@ fn run() -> eyre::Result<()> {
             .prompt_skippable()?)
     }
 
-    /// Note: Doesn't set `content_path`
-    fn setup_update_handle(
-        handle: steamworks::UpdateHandle<steamworks::ClientManager>,
-        workshop_item: &WorkshopItemArgs,
-    ) -> eyre::Result<steamworks::UpdateHandle<steamworks::ClientManager>> {
-        let mut handle = handle
-            .visibility(workshop_item.visibility.unwrap_or_default().into())
-            .tags(workshop_item.tags.iter().collect_vec(), false);
+    /// Returns whether the workshop page was opened for the user to accept the agreement.
+    fn warn_legal_agreement(item_id: u64, no_prompt: bool) -> eyre::Result<bool> {
+        warn!(item_id, "Workshop legal agreement needs to be accepted");
+        output::emit(Event::LegalAgreementRequired { item_id });
+        eprintln!(
+            "{}",
+            "[!] You need to accept the Steam Workshop legal agreement. The item will stay hidden until you do!"
+                .yellow()
+                .bold()
+        );
 
-        if let Some(title) = &workshop_item.title {
-            handle = handle.title(title);
+        if no_prompt {
+            return Ok(false);
         }
-        if let Some(description) = &workshop_item.description {
-            handle = handle.description(description);
+
+        // The item's page prompts for the agreement, as recommended by the Steamworks docs
+        let open_page = inquire::Confirm::new("Open the workshop page to accept the agreement?")
+            .with_default(true)
+            .prompt_skippable()?
+            .unwrap_or_default();
+        if open_page {
+            eprintln!("{}", "[+] Opening workshop page...".green());
+            open_workshop_page(item_id)?;
         }
-        if let Some(preview_path) = &workshop_item.preview_path {
-            is_valid_preview_type(&preview_path)?;
-            handle = handle.preview_path(&preview_path.canonicalize()?);
+
+        Ok(open_page)
+    }
+
+    fn stage_content(
+        content_path: &Path,
+        workshop_item: &WorkshopItemArgs,
+        to: &Path,
+    ) -> eyre::Result<Vec<StagedFile>> {
+        output::emit(Event::StagingStarted {
+            content_path: content_path.to_path_buf(),
+        });
+
+        let staged_files = workshop::copy_filtered_content(
+            content_path,
+            to,
+            Some(workshop_item.content_filter.globs.as_slice()),
+            Some(
+                workshop_item
+                    .content_filter
+                    .ignore_files
+                    .iter()
+                    .collect_vec()
+                    .as_slice(),
+            ),
+        )?;
+
+        output::emit(Event::StagingFinished {
+            file_count: staged_files.len(),
+            total_bytes: staged_files.iter().map(|it| it.size).sum(),
+        });
+
+        Ok(staged_files)
+    }
+
+    /// Note: Doesn't set `content_path` and `preview_changes`
+    ///
+    /// Previews converted by `--preview-auto-convert` are written to `convert_dir`.
+    fn setup_item_update(
+        workshop_item: &WorkshopItemArgs,
+        convert_dir: &Path,
+    ) -> eyre::Result<ItemUpdate> {
+        let convert_dir = workshop_item.preview_auto_convert.then_some(convert_dir);
+        let preview_path = match &workshop_item.preview_path {
+            Some(preview_path) => {
+                Some(preview::prepare_preview_image(preview_path, convert_dir)?.canonicalize()?)
+            }
+            None => None,
+        };
+
+        let metadata = match &workshop_item.metadata_file {
+            Some(metadata_file) => Some(fs_err::read_to_string(metadata_file)?),
+            None => workshop_item.metadata.clone(),
+        };
+        if let Some(metadata) = &metadata {
+            check_metadata(metadata)?;
         }
 
-        Ok(handle)
+        Ok(ItemUpdate {
+            title: workshop_item.title.clone(),
+            description: workshop_item.description.clone(),
+            visibility: Some(workshop_item.visibility.unwrap_or_default()),
+            tags: Some(workshop_item.tags.clone()),
+            preview_path,
+            content_path: None,
+            change_note: workshop_item.change_log.clone(),
+            key_value_tags: workshop_item.key_value_tags.iter().cloned().collect(),
+            removed_key_value_tags: workshop_item.removed_key_value_tags.clone(),
+            metadata,
+            language: None,
+        })
+    }
+
+    /// Fills in the change note from `--change-log-from`, if given.
+    fn resolve_change_log_from(
+        workshop_item: &mut WorkshopItemArgs,
+        content_path: &Path,
+        last_uploaded_commit: Option<&str>,
+    ) -> eyre::Result<()> {
+        let Some(source) = &workshop_item.change_log_from else {
+            return Ok(());
+        };
+        workshop_item.change_log =
+            changelog::change_log_from(source, content_path, last_uploaded_commit)?;
+        if workshop_item.change_log.is_none() {
+            eprintln!(
+                "{}",
+                "[!] No commits since the last upload, leaving the change note empty.".yellow()
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns whether the user still needs to accept the Workshop legal agreement.
+    fn submit_localized_updates(
+        backend: &dyn WorkshopBackend,
+        app_id: AppId,
+        item_id: u64,
+        localized_updates: &[ItemUpdate],
+    ) -> eyre::Result<bool> {
+        let mut needs_legal_agreement = false;
+        for update in localized_updates {
+            eprintln!(
+                "{}",
+                format!(
+                    "[-] Updating {} title and description...",
+                    update.language.as_deref().unwrap_or_default()
+                )
+                .cyan()
+            );
+            let (_, needs_legal_agreement_on_update) =
+                backend.submit_update(app_id, item_id, update, &mut |_| {})?;
+            needs_legal_agreement |= needs_legal_agreement_on_update;
+        }
+        Ok(needs_legal_agreement)
     }
 
     let visibility_prompt = inquire::Select::new(
@@ -140,21 +360,7 @@ fn run() -> eyre::Result<()> {
 
     match cli.command {
         cli::Command::Create(mut command) => {
-            let app_id =
-                command
-                    .app_id
-                    .clone()
-                    .map(|it| Ok(it))
-                    .unwrap_or_else(|| -> eyre::Result<_> {
-                        if cli.no_prompt {
-                            bail!("AppId is required");
-                        } else {
-                            Ok(exit_on_none!(
-                                inquire::CustomType::<u32>::new("AppId").prompt_skippable()?
-                            )
-                            .into())
-                        }
-                    })?;
+            let app_id = app_id_or_prompt(command.app_id, cli.no_prompt)?;
 
             // Verify tags passed from cli
             let valid_tags = config.inner.valid_tags.get(&app_id);
@@ -162,18 +368,8 @@ fn run() -> eyre::Result<()> {
                 check_tags_are_predefined(&command.workshop_item.tags, &valid_tags)?;
             }
 
-            let content_path = command
-                .workshop_item
-                .content_path
-                .clone()
-                .map(|it| Ok(it))
-                .unwrap_or_else(|| {
-                    if cli.no_prompt {
-                        bail!("Path to Content Folder is required")
-                    } else {
-                        inquire_content_path()
-                    }
-                })?;
+            let content_path =
+                content_path_or_prompt(command.workshop_item.content_path.clone(), cli.no_prompt)?;
 
             if content_path.join(WORKSHOP_METADATA_FILENAME).is_file() {
                 eprintln!(
@@ -183,6 +379,24 @@ fn run() -> eyre::Result<()> {
                 quit::with_code(exitcode::USAGE as u8);
             }
 
+            let localized_updates = workshop::localized_updates(
+                &workshop::load_localizations(None, &content_path)?,
+                &content_path,
+                command.workshop_item.description_format.unwrap_or_default(),
+            )?;
+            let converted_previews_dir = tempfile::TempDir::new()?;
+            let previews = preview::resolve_previews(&[], &command.workshop_item, &content_path)?;
+            let preview_changes = preview::diff_previews(
+                &previews,
+                &[],
+                &content_path,
+                command
+                    .workshop_item
+                    .preview_auto_convert
+                    .then_some(converted_previews_dir.path()),
+            )?;
+            resolve_change_log_from(&mut command.workshop_item, &content_path, None)?;
+
             // todo: validate title and description length
 
             if !cli.no_prompt {
@@ -236,23 +450,51 @@ fn run() -> eyre::Result<()> {
                     command.workshop_item.visibility =
                         visibility_prompt.clone().prompt_skippable()?;
                 }
-                if command.workshop_item.change_log.is_none() {
+                if command.workshop_item.change_log.is_none()
+                    && command.workshop_item.change_log_from.is_none()
+                {
                     command.workshop_item.change_log =
                         inquire::Editor::new("Changelog").prompt_skippable()?;
                 }
             }
+            // After the prompts, so that a description typed in is converted too
+            workshop::render_description(&mut command.workshop_item)?;
+
+            if command.dry_run {
+                eprintln!("{}", "[-] Preparing workshop content...".cyan());
+
+                let prepared_content_dir = tempfile::TempDir::new()?;
+                let staged_files = stage_content(
+                    &content_path,
+                    &command.workshop_item,
+                    prepared_content_dir.path(),
+                )?;
+                let update = ItemUpdate {
+                    preview_changes,
+                    ..setup_item_update(&command.workshop_item, converted_previews_dir.path())?
+                };
+
+                workshop::print_update_plan(&update, &localized_updates, None, Some(&staged_files));
+                eprintln!("{}", "[+] Dry run, no workshop item was created.".green());
+
+                return Ok(());
+            }
 
             eprintln!("{}", "[-] Creating workshop item...".cyan());
 
-            let (client, single) = workshop::steamworks_client_init(app_id)?;
-            let (file_id, _) = workshop::create_item_with_metadata_file(
-                &client,
-                &single,
+            let backend = backend::init(&backend_options, app_id)?;
+            let (file_id, needs_legal_agreement) = workshop::create_item_with_metadata_file(
+                backend.as_ref(),
                 app_id,
                 &content_path,
-                &command.workshop_item.tags,
+                &command.workshop_item,
+                &previews,
             )?;
 
+            output::emit(Event::ItemCreated {
+                item_id: file_id.0,
+                app_id: app_id.0,
+            });
             eprintln!(
                 "{} {}{}",
                 "[+] Created a new workshop item!".green(),
@@ -262,85 +504,136 @@ fn run() -> eyre::Result<()> {
             eprintln!("{}", "[-] Preparing workshop content...".cyan());
 
             let prepared_content_dir = tempfile::TempDir::new()?;
-            workshop::copy_filtered_content(
-                &content_path,
-                prepared_content_dir.path(),
-                Some(command.workshop_item.globs.as_slice()),
-                Some(
-                    command
-                        .workshop_item
-                        .ignore_files
-                        .iter()
-                        .collect_vec()
-                        .as_slice(),
-                ),
-            )?;
+            let upload_result = (|| -> eyre::Result<_> {
+                let staged_files = stage_content(
+                    &content_path,
+                    &command.workshop_item,
+                    prepared_content_dir.path(),
+                )?;
+                let manifest = ContentManifest::from_staged(
+                    &staged_files,
+                    prepared_content_dir.path(),
+                    &content_path,
+                )?;
 
-            eprintln!(
-                "{}",
-                "[+] Made a staging copy of the workshop content folder.".green()
-            );
+                eprintln!(
+                    "{}",
+                    "[+] Made a staging copy of the workshop content folder.".green()
+                );
 
-            let handle = client
-                .ugc()
-                .start_item_update(app_id.into(), file_id)
-                .content_path(prepared_content_dir.path());
+                let update = ItemUpdate {
+                    content_path: Some(prepared_content_dir.path().to_path_buf()),
+                    preview_changes,
+                    ..setup_item_update(&command.workshop_item, converted_previews_dir.path())?
+                };
 
-            eprintln!("{}", "[-] Updating workshop item...".cyan());
+                // Before the content upload, so that unsupported localizations fail early
+                let needs_legal_agreement_on_localization = submit_localized_updates(
+                    backend.as_ref(),
+                    app_id,
+                    file_id.0,
+                    &localized_updates,
+                )?;
 
-            setup_update_handle(handle, &command.workshop_item)?.submit_blocking(
-                &single,
-                command
-                    .workshop_item
-                    .change_log
-                    .as_ref()
-                    .map(|it| it.as_str()),
-            )?;
+                eprintln!("{}", "[-] Updating workshop item...".cyan());
+
+                let mut progress = UploadProgressReporter::new(file_id.0);
+                let (_, needs_legal_agreement_on_update) =
+                    backend
+                        .submit_update(app_id, file_id.0, &update, &mut |it| progress.update(it))?;
+                Ok((
+                    manifest,
+                    needs_legal_agreement_on_localization || needs_legal_agreement_on_update,
+                ))
+            })();
+
+            let needs_legal_agreement = match upload_result {
+                Ok((manifest, needs_legal_agreement_on_update)) => {
+                    manifest.store_in(&content_path)?;
+                    needs_legal_agreement || needs_legal_agreement_on_update
+                }
+                Err(err) => {
+                    eprintln!(
+                        "{}",
+                        format!("[!] Failed to upload the new workshop item: {err}").red()
+                    );
+
+                    // Otherwise, an empty item is left behind on the Workshop
+                    let delete_orphan = cli.no_prompt
+                        || inquire::Confirm::new("Delete the newly created, empty workshop item?")
+                            .with_default(true)
+                            .prompt_skippable()?
+                            .unwrap_or_default();
+                    if delete_orphan {
+                        eprintln!("{}", "[-] Deleting workshop item...".cyan());
+                        workshop::delete_item_with_metadata_file(
+                            backend.as_ref(),
+                            file_id.0,
+                            Some(&content_path),
+                            false,
+                        )?;
+                        output::emit(Event::ItemDeleted { item_id: file_id.0 });
+                        eprintln!("{}", "[+] Workshop item deleted!".green());
+                    }
+
+                    return Err(err);
+                }
+            };
 
+            output::emit(Event::UpdateSubmitted { item_id: file_id.0 });
             eprintln!("{}", "[+] Workshop item updated!".green());
 
             info!(item_id = file_id.0, "Workshop item updated");
 
-            if config.inner.open_item_page_on_complete {
+            let mut page_opened = false;
+            if needs_legal_agreement {
+                page_opened = warn_legal_agreement(file_id.0, cli.no_prompt)?;
+            }
+
+            if config.inner.open_item_page_on_complete && !page_opened {
                 eprintln!("{}", "[+] Opening workshop page...".green());
                 open_workshop_page(file_id.0)?;
             }
-        }
-        cli::Command::Update(mut command) => {
-            let content_path = command
-                .workshop_item
-                .content_path
-                .clone()
-                .map(|it| Ok(it))
-                .unwrap_or_else(|| {
-                    if cli.no_prompt {
-                        bail!("Path to Content Folder is required")
-                    } else {
-                        inquire_content_path()
-                    }
-                })?;
 
-            if !content_path.join(WORKSHOP_METADATA_FILENAME).is_file() {
-                eprintln!(
-                    "Missing metadata file `{}` from {:?}",
-                    WORKSHOP_METADATA_FILENAME, content_path
-                );
-                quit::with_code(exitcode::USAGE as u8);
+            if needs_legal_agreement && cli.no_prompt {
+                quit::with_code(LEGAL_AGREEMENT_EXIT_CODE);
             }
+        }
+        cli::Command::Update(mut command) => {
+            let content_path =
+                content_path_or_prompt(command.workshop_item.content_path.clone(), cli.no_prompt)?;
 
-            // todo: item update status? EItemUpdateStatus
+            let mut workshop_item_cfg = load_item_config(&content_path)?;
 
-            let workshop_item_cfg =
-                WorkshopItemConfig::try_load_path(content_path.join("workshop.toml"))?;
+            let cli_item_args = command.workshop_item.clone();
 
             // Using tags from metadata file only if no tag cli args are passed
             let update_tags = command.workshop_item.tags.len() != 0;
-            if !update_tags {
-                command
-                    .workshop_item
-                    .tags
-                    .extend_from_slice(&workshop_item_cfg.tags);
-            }
+            workshop::apply_item_config(
+                &mut command.workshop_item,
+                &workshop_item_cfg,
+                &content_path,
+            )?;
+            let git_guards = workshop_item_cfg
+                .git
+                .clone()
+                .unwrap_or_else(|| config.inner.git.clone());
+            git::check_guards(&git_guards, &content_path)?;
+            workshop::render_description(&mut command.workshop_item)?;
+
+            resolve_change_log_from(
+                &mut command.workshop_item,
+                &content_path,
+                ContentManifest::load_from(&content_path)?
+                    .and_then(|it| it.commit)
+                    .as_deref(),
+            )?;
+
+            let localized_updates = workshop::localized_updates(
+                &workshop::load_localizations(Some(&workshop_item_cfg), &content_path)?,
+                &content_path,
+                command.workshop_item.description_format.unwrap_or_default(),
+            )?;
 
             let valid_tags = config
                 .inner
@@ -350,25 +643,16 @@ fn run() -> eyre::Result<()> {
                 check_tags_are_predefined(&command.workshop_item.tags, &valid_tags)?;
             }
 
-            let (client, single) = workshop::steamworks_client_init(workshop_item_cfg.app_id)?;
-
-            let (tx, rx) = mpsc::channel();
-            client
-                .ugc()
-                .query_item(workshop_item_cfg.item_id.into())?
-                .include_long_desc(true)
-                .fetch(move |result| {
-                    _ = tx
-                        .send(result.map(|it| it.iter().find_map(|it| it)).ok().flatten())
-                        .inspect_err(|e| error!(%e));
-                });
+            let backend = backend::init(&backend_options, workshop_item_cfg.app_id.into())?;
 
-            let item_info = run_callbacks_blocking!(single, rx).with_context(|| {
-                format!(
-                    "Failed to receive query result for item id: {}",
-                    workshop_item_cfg.item_id
-                )
-            })?;
+            let item_info = backend
+                .query_item(workshop_item_cfg.item_id)?
+                .with_context(|| {
+                    format!(
+                        "Failed to receive query result for item id: {}",
+                        workshop_item_cfg.item_id
+                    )
+                })?;
 
             if !cli.no_prompt {
                 if command.workshop_item.title.is_none() {
@@ -389,46 +673,66 @@ fn run() -> eyre::Result<()> {
                             .prompt_skippable()?
                             .unwrap_or_default();
                 }
-                if !command.no_content_update && command.workshop_item.change_log.is_none() {
+                if !command.no_content_update
+                    && command.workshop_item.change_log.is_none()
+                    && command.workshop_item.change_log_from.is_none()
+                {
                     command.workshop_item.change_log =
                         inquire::Editor::new("Changelog").prompt_skippable()?;
                 }
             }
 
-            command.workshop_item.title.get_or_insert(item_info.title);
+            command
+                .workshop_item
+                .title
+                .get_or_insert_with(|| item_info.title.clone());
             command
                 .workshop_item
                 .description
-                .get_or_insert(item_info.description);
+                .get_or_insert_with(|| item_info.description.clone());
             command
                 .workshop_item
                 .visibility
-                .get_or_insert(item_info.visibility.into());
+                .get_or_insert(item_info.visibility);
 
-            let mut handle = client.ugc().start_item_update(
-                workshop_item_cfg.app_id.into(),
-                workshop_item_cfg.item_id.into(),
-            );
+            let converted_previews_dir = tempfile::TempDir::new()?;
+            let previews = preview::resolve_previews(
+                &workshop_item_cfg.previews,
+                &command.workshop_item,
+                &content_path,
+            )?;
+            let mut update = ItemUpdate {
+                preview_changes: preview::diff_previews(
+                    &previews,
+                    &item_info.additional_previews,
+                    &content_path,
+                    command
+                        .workshop_item
+                        .preview_auto_convert
+                        .then_some(converted_previews_dir.path()),
+                )?,
+                ..setup_item_update(&command.workshop_item, converted_previews_dir.path())?
+            };
 
             eprintln!("{}", "[-] Preparing workshop content...".cyan());
 
             let prepared_content_dir;
+            let mut staged_files = None;
+            let mut manifest = None;
             if !command.no_content_update {
                 prepared_content_dir = tempfile::TempDir::new()?;
-                workshop::copy_filtered_content(
+                let files = stage_content(
                     &content_path,
+                    &command.workshop_item,
                     prepared_content_dir.path(),
-                    Some(command.workshop_item.globs.as_slice()),
-                    Some(
-                        command
-                            .workshop_item
-                            .ignore_files
-                            .iter()
-                            .collect_vec()
-                            .as_slice(),
-                    ),
                 )?;
-                handle = handle.content_path(prepared_content_dir.path()); // Symlinked files don't work unfortunately
+                manifest = Some(ContentManifest::from_staged(
+                    &files,
+                    prepared_content_dir.path(),
+                    &content_path,
+                )?);
+                staged_files = Some(files);
+                update.content_path = Some(prepared_content_dir.path().to_path_buf());
                 eprintln!(
                     "{}",
                     "[+] Made a staging copy of the workshop content folder.".green()
@@ -440,23 +744,113 @@ fn run() -> eyre::Result<()> {
                 );
             }
 
+            if let Some(generator) = &workshop_item_cfg.generator {
+                eprintln!("{}", "[-] Running metadata generator...".cyan());
+                let generated = generator::run_generator(
+                    generator,
+                    &content_path,
+                    update.content_path.as_deref(),
+                    workshop_item_cfg.app_id,
+                    workshop_item_cfg.item_id,
+                )?;
+                generator::apply_generated_metadata(&mut update, generated, &cli_item_args);
+                eprintln!("{}", "[+] Generated item metadata.".green());
+            }
+
+            if let (Some(new_manifest), Some(last_manifest)) =
+                (&manifest, ContentManifest::load_from(&content_path)?)
+            {
+                let diff = last_manifest.diff(new_manifest);
+                if diff.is_empty() && !command.force_content_update {
+                    update.content_path = None;
+                    staged_files = None;
+                    manifest = None;
+                    output::emit(Event::ContentUnchanged {
+                        item_id: workshop_item_cfg.item_id,
+                    });
+                    eprintln!(
+                        "{}",
+                        "[+] Content is unchanged since the last upload, only updating metadata."
+                            .green()
+                    );
+                } else {
+                    diff.print_summary();
+                    if update.change_note.as_deref().unwrap_or_default().is_empty() {
+                        update.change_note = Some(
+                            diff.change_note(workshop_item_cfg.change_note_template.as_deref()),
+                        );
+                        eprintln!(
+                            "{}",
+                            "[+] Generated a change note from the content changes.".green()
+                        );
+                    }
+                }
+            }
+
+            if command.dry_run {
+                workshop::print_update_plan(
+                    &update,
+                    &localized_updates,
+                    Some(&item_info),
+                    staged_files.as_deref(),
+                );
+                eprintln!("{}", "[+] Dry run, nothing was submitted.".green());
+
+                return Ok(());
+            }
+
+            // Before the content upload, so that unsupported localizations fail early
+            let needs_legal_agreement_on_localization = submit_localized_updates(
+                backend.as_ref(),
+                workshop_item_cfg.app_id.into(),
+                workshop_item_cfg.item_id,
+                &localized_updates,
+            )?;
+
             eprintln!("{}", "[-] Updating workshop item...".cyan());
 
-            let (file_id, _) = setup_update_handle(handle, &command.workshop_item)?
-                .submit_blocking(
-                    &single,
-                    // This is such a horrible API, like `Option<&str>`? Seriously?
-                    command
-                        .workshop_item
-                        .change_log
-                        .as_ref()
-                        .map(|it| it.as_str()),
+            let (file_id, needs_legal_agreement) = {
+                let mut progress = UploadProgressReporter::new(workshop_item_cfg.item_id);
+                let (file_id, needs_legal_agreement_on_update) = backend.submit_update(
+                    workshop_item_cfg.app_id.into(),
+                    workshop_item_cfg.item_id,
+                    &update,
+                    &mut |it| progress.update(it),
                 )?;
+                (
+                    file_id,
+                    needs_legal_agreement_on_localization || needs_legal_agreement_on_update,
+                )
+            };
 
+            output::emit(Event::UpdateSubmitted { item_id: file_id.0 });
             eprintln!("{}", "[+] Workshop item updated!".green());
 
             info!(item_id = file_id.0, "Workshop item updated");
 
+            // Along with the uploaded commit. Change notes only go along with content updates, so commits of metadata
+            // only updates are still due one
+            if let Some(manifest) = &manifest {
+                manifest.store_in(&content_path)?;
+            }
+            if update.content_path.is_some() && git_guards.tag_uploads {
+                let tag = git::tag_upload(&content_path, file_id.0)?;
+                eprintln!(
+                    "{}",
+                    format!("[+] Tagged the uploaded commit as `{tag}`.").green()
+                );
+            }
+
+            if previews != workshop_item_cfg.previews {
+                workshop_item_cfg.previews = previews;
+                workshop_item_cfg.store_path(content_path.join(WORKSHOP_METADATA_FILENAME))?;
+                eprintln!(
+                    "{}",
+                    format!("[+] Saved the additional previews to `{WORKSHOP_METADATA_FILENAME}`.")
+                        .green()
+                );
+            }
+
             if update_tags {
                 if !cli.no_prompt && inquire::Confirm::new(
                     &format!("Do you want to overwrite tags in `{WORKSHOP_METADATA_FILENAME}` with the ones provided?"),
@@ -472,10 +866,302 @@ fn run() -> eyre::Result<()> {
                 }
             }
 
-            if config.inner.open_item_page_on_complete {
+            let mut page_opened = false;
+            if needs_legal_agreement {
+                page_opened = warn_legal_agreement(file_id.0, cli.no_prompt)?;
+            }
+
+            if config.inner.open_item_page_on_complete && !page_opened {
                 eprintln!("{}", "[+] Opening workshop page...".green());
                 open_workshop_page(file_id.0)?;
             }
+
+            if needs_legal_agreement && cli.no_prompt {
+                quit::with_code(LEGAL_AGREEMENT_EXIT_CODE);
+            }
+        }
+        cli::Command::List(command) => {
+            let app_id = app_id_or_prompt(command.app_id, cli.no_prompt)?;
+            let backend = backend::init(&backend_options, app_id)?;
+
+            let mut page = command.page;
+            loop {
+                let item_page = backend.query_user_items(app_id, page)?;
+                let page_count = item_page.total.div_ceil(ITEMS_PER_PAGE).max(1);
+
+                if item_page.items.is_empty() {
+                    eprintln!("{}", "[+] No workshop items found.".green());
+                    break;
+                }
+
+                eprintln!(
+                    "{}",
+                    format!(
+                        "[+] Page {page}/{page_count} ({} items in total)",
+                        item_page.total
+                    )
+                    .green()
+                );
+                for item in &item_page.items {
+                    if output::is_json() {
+                        output::emit(Event::Item(item.clone()));
+                        continue;
+                    }
+
+                    println!(
+                        "{}  {}  [{}]  updated {}",
+                        item.item_id,
+                        item.title.bold(),
+                        item.visibility,
+                        format_timestamp(item.time_updated)
+                    );
+                    if !item.tags.is_empty() {
+                        println!("    tags: {}", item.tags.iter().join(", "));
+                    }
+                }
+
+                if page >= page_count {
+                    break;
+                }
+                let next_page = command.all
+                    || (!cli.no_prompt
+                        && inquire::Confirm::new("Show next page?")
+                            .with_default(true)
+                            .prompt_skippable()?
+                            .unwrap_or_default());
+                if !next_page {
+                    break;
+                }
+                page += 1;
+            }
+        }
+        cli::Command::Info(command) => {
+            let (app_id, item_id, _) = resolve_item(&command.item, cli.no_prompt)?;
+            let backend = backend::init(&backend_options, app_id)?;
+
+            let item_info = backend.query_item(item_id)?.with_context(|| {
+                format!("Failed to receive query result for item id: {}", item_id)
+            })?;
+
+            if output::is_json() {
+                output::emit(Event::Item(item_info));
+                return Ok(());
+            }
+
+            println!("{} {}", "Id:".bold(), item_info.item_id);
+            println!("{} {}", "AppId:".bold(), item_info.app_id);
+            println!("{} {}", "Title:".bold(), item_info.title);
+            println!("{} {}", "Visibility:".bold(), item_info.visibility);
+            println!("{} {}", "Tags:".bold(), item_info.tags.iter().join(", "));
+            println!("{} {} bytes", "File Size:".bold(), item_info.file_size);
+            println!(
+                "{} {}",
+                "Preview:".bold(),
+                item_info.preview_url.as_deref().unwrap_or("-")
+            );
+            println!(
+                "{} {}",
+                "Created:".bold(),
+                format_timestamp(item_info.time_created)
+            );
+            println!(
+                "{} {}",
+                "Updated:".bold(),
+                format_timestamp(item_info.time_updated)
+            );
+            println!("{} {}", "Owner:".bold(), item_info.owner);
+            println!(
+                "{} {}",
+                "Subscriptions:".bold(),
+                item_info.statistics.subscriptions
+            );
+            println!("{} {}", "Favorites:".bold(), item_info.statistics.favorites);
+            println!("{} {}", "Followers:".bold(), item_info.statistics.followers);
+            println!(
+                "{} +{} / -{}",
+                "Votes:".bold(),
+                item_info.statistics.upvotes,
+                item_info.statistics.downvotes
+            );
+            println!("{}\n{}", "Description:".bold(), item_info.description);
+        }
+        cli::Command::Delete(command) => {
+            let (app_id, item_id, content_path) = resolve_item(&command.item, cli.no_prompt)?;
+
+            if !cli.no_prompt
+                && !inquire::Confirm::new(&format!(
+                    "Are you sure you want to delete workshop item {item_id}? This can't be undone."
+                ))
+                .with_default(false)
+                .prompt_skippable()?
+                .unwrap_or_default()
+            {
+                quit::with_code(exitcode::OK as u8);
+            }
+
+            let backend = backend::init(&backend_options, app_id)?;
+
+            eprintln!("{}", "[-] Deleting workshop item...".cyan());
+
+            workshop::delete_item_with_metadata_file(
+                backend.as_ref(),
+                item_id,
+                content_path.as_ref(),
+                !command.remove_metadata,
+            )?;
+
+            output::emit(Event::ItemDeleted { item_id });
+            eprintln!("{}", "[+] Workshop item deleted!".green());
+        }
+        cli::Command::Pull(command) => {
+            let content_path = content_path_or_prompt(command.content_path.clone(), cli.no_prompt)?;
+            let mut workshop_item_cfg = load_item_config(&content_path)?;
+            if command.description_file.is_some() {
+                workshop_item_cfg.description_file = command.description_file;
+            }
+
+            let backend = backend::init(&backend_options, workshop_item_cfg.app_id.into())?;
+
+            let item_info = backend
+                .query_item(workshop_item_cfg.item_id)?
+                .with_context(|| {
+                    format!(
+                        "Failed to receive query result for item id: {}",
+                        workshop_item_cfg.item_id
+                    )
+                })?;
+
+            if !cli.no_prompt
+                && !inquire::Confirm::new(&format!(
+                    "Overwrite local metadata in `{WORKSHOP_METADATA_FILENAME}` with the remote one?"
+                ))
+                .with_default(true)
+                .prompt_skippable()?
+                .unwrap_or_default()
+            {
+                quit::with_code(exitcode::OK as u8);
+            }
+
+            workshop::pull_item_metadata(item_info, &mut workshop_item_cfg, &content_path)?;
+            workshop::pull_item_localizations(
+                backend.as_ref(),
+                &mut workshop_item_cfg,
+                &content_path,
+                &command.languages,
+            )?;
+            workshop_item_cfg.store_path(content_path.join(WORKSHOP_METADATA_FILENAME))?;
+
+            output::emit(Event::MetadataPulled {
+                item_id: workshop_item_cfg.item_id,
+            });
+            eprintln!(
+                "{}",
+                format!("[+] Pulled remote metadata into `{WORKSHOP_METADATA_FILENAME}`.").green()
+            );
+        }
+        cli::Command::Migrate(command) => {
+            let migrated = migration::migrate_workshop_metadata_in_tree(&command.dir)?;
+
+            for (path, version) in &migrated {
+                output::emit(Event::Migrated {
+                    path: path.clone(),
+                    from_version: *version,
+                    to_version: migration::WORKSHOP_SCHEMA_VERSION,
+                });
+                eprintln!(
+                    "{} {:?} (v{} -> v{})",
+                    "[+] Migrated".green(),
+                    path,
+                    version,
+                    migration::WORKSHOP_SCHEMA_VERSION
+                );
+            }
+            eprintln!(
+                "{}",
+                format!("[+] Migrated {} metadata file(s).", migrated.len()).green()
+            );
+        }
+        cli::Command::LsFiles(command) => {
+            let content_path = content_path_or_prompt(command.content_path.clone(), cli.no_prompt)?;
+
+            let (included, excluded) = workshop::list_content_files(
+                &content_path,
+                Some(command.content_filter.globs.as_slice()),
+                Some(command.content_filter.ignore_files.as_slice()),
+            )?;
+
+            if command.excluded {
+                for entry in &excluded {
+                    if output::is_json() {
+                        output::emit(Event::Excluded {
+                            path: entry.path.to_string(),
+                            is_dir: entry.is_dir,
+                            rule: entry.rule.to_string(),
+                        });
+                        continue;
+                    }
+
+                    println!(
+                        "{}{}  {}",
+                        entry.path,
+                        if entry.is_dir { "/" } else { "" },
+                        format!("({})", entry.rule).dimmed()
+                    );
+                }
+                eprintln!(
+                    "{}",
+                    format!("[+] {} entries excluded.", excluded.len()).green()
+                );
+            } else {
+                for file in &included {
+                    if output::is_json() {
+                        output::emit(Event::File {
+                            path: file.path.to_string(),
+                            size: file.size,
+                        });
+                        continue;
+                    }
+
+                    println!("{}  {}", file.path, format_size(file.size).dimmed());
+                }
+                eprintln!(
+                    "{}",
+                    format!(
+                        "[+] {} files, {} in total.",
+                        included.len(),
+                        format_size(included.iter().map(|it| it.size).sum())
+                    )
+                    .green()
+                );
+            }
+        }
+        cli::Command::Render(command) => {
+            let description = match &command.file {
+                Some(file) => bbcode::markdown_to_bbcode(&fs_err::read_to_string(file)?),
+                None => {
+                    let content_path =
+                        content_path_or_prompt(command.content_path.clone(), cli.no_prompt)?;
+                    let workshop_item_cfg = load_item_config(&content_path)?;
+                    let description = match &workshop_item_cfg.description_file {
+                        Some(description_file) => {
+                            fs_err::read_to_string(content_path.join(description_file))?
+                        }
+                        None => workshop_item_cfg.description.clone().with_context(|| {
+                            format!("No description is set in `{WORKSHOP_METADATA_FILENAME}`")
+                        })?,
+                    };
+                    workshop::convert_description(
+                        description,
+                        workshop_item_cfg.description_format.unwrap_or_default(),
+                    )
+                }
+            };
+
+            if output::is_json() {
+                output::emit(Event::Rendered { description });
+            } else {
+                println!("{description}");
+            }
         }
     }
 
@@ -0,0 +1,273 @@
+use std::{collections::BTreeMap, path::Path};
+
+use color_eyre::{eyre, owo_colors::OwoColorize};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    changelog::truncate_change_note, config::Config, defines::WORKSHOP_MANIFEST_FILENAME, git,
+    workshop::StagedFile,
+};
+
+/// Lines listed per section of a generated change note, before the rest is summarized.
+const CHANGE_NOTE_MAX_LINES: usize = 15;
+/// Directories with more changed files than this are listed as a whole in generated change notes.
+const CHANGE_NOTE_MAX_FILES_PER_DIR: usize = 3;
+/// Used when no template is configured.
+const DEFAULT_CHANGE_NOTE_TEMPLATE: &str = "{changes}";
+
+/// Files of an item's last successful content upload, stored as `workshop.lock` next to `workshop.toml`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentManifest {
+    /// Commit checked out in the content folder's git repository during the upload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Keyed by path relative to the content root.
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub size: u64,
+    /// BLAKE3 hash, hex encoded.
+    pub hash: String,
+}
+
+impl Config for ContentManifest {}
+
+impl ContentManifest {
+    /// Hashes the staged copies of `staged_files` in `staging_path`, staged from `content_path`.
+    pub fn from_staged(
+        staged_files: &[StagedFile],
+        staging_path: &Path,
+        content_path: &Path,
+    ) -> eyre::Result<Self> {
+        let mut files = BTreeMap::new();
+        for file in staged_files {
+            let hash = blake3::Hasher::new()
+                .update_reader(fs_err::File::open(file.path.to_path(staging_path))?)?
+                .finalize();
+            files.insert(
+                file.path.to_string(),
+                ManifestEntry {
+                    size: file.size,
+                    hash: hash.to_hex().to_string(),
+                },
+            );
+        }
+        Ok(Self {
+            commit: git::head_commit(content_path)?,
+            files,
+        })
+    }
+
+    /// The manifest of the last upload from `content_path`, if there was one.
+    pub fn load_from(content_path: impl AsRef<Path>) -> eyre::Result<Option<Self>> {
+        let path = content_path.as_ref().join(WORKSHOP_MANIFEST_FILENAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(Self::try_load_path(path)?))
+    }
+
+    pub fn store_in(&self, content_path: impl AsRef<Path>) -> eyre::Result<()> {
+        self.store_path(content_path.as_ref().join(WORKSHOP_MANIFEST_FILENAME))
+    }
+
+    /// What changed going from `self` to `new`.
+    pub fn diff(&self, new: &Self) -> ManifestDiff {
+        let mut diff = ManifestDiff::default();
+        for (path, entry) in &new.files {
+            match self.files.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(old) if old != entry => diff.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = self
+            .files
+            .keys()
+            .filter(|it| !new.files.contains_key(*it))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+/// Paths of files that changed between two manifests, sorted.
+#[derive(Debug, Default, Clone)]
+pub struct ManifestDiff {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ManifestDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    /// A change note listing the changed files, grouped by directory, in place of `{changes}` in `template`.
+    ///
+    /// `{added}`, `{modified}` and `{removed}` in the template are replaced with the number of files in each.
+    pub fn change_note(&self, template: Option<&str>) -> String {
+        let changes = [
+            ("Added", &self.added),
+            ("Modified", &self.modified),
+            ("Removed", &self.removed),
+        ]
+        .into_iter()
+        .filter(|(_, paths)| !paths.is_empty())
+        .map(|(section, paths)| {
+            format!(
+                "{section}:\n{}",
+                summarize_paths(paths)
+                    .iter()
+                    .map(|it| format!("- {it}"))
+                    .join("\n")
+            )
+        })
+        .join("\n\n");
+
+        truncate_change_note(
+            template
+                .unwrap_or(DEFAULT_CHANGE_NOTE_TEMPLATE)
+                .replace("{added}", &self.added.len().to_string())
+                .replace("{modified}", &self.modified.len().to_string())
+                .replace("{removed}", &self.removed.len().to_string())
+                .replace("{changes}", &changes),
+        )
+    }
+
+    pub fn print_summary(&self) {
+        eprintln!(
+            "{}",
+            format!(
+                "[+] Content changed since the last upload: {} added, {} modified, {} removed.",
+                self.added.len(),
+                self.modified.len(),
+                self.removed.len()
+            )
+            .green()
+        );
+        for path in &self.added {
+            eprintln!("    {} {path}", "+".green());
+        }
+        for path in &self.modified {
+            eprintln!("    {} {path}", "~".yellow());
+        }
+        for path in &self.removed {
+            eprintln!("    {} {path}", "-".red());
+        }
+    }
+}
+
+/// Groups `paths` by their directory, collapsing directories with many of them, and caps the number of lines.
+fn summarize_paths(paths: &[String]) -> Vec<String> {
+    let by_dir = paths
+        .iter()
+        .into_group_map_by(|it| it.rsplit_once('/').map(|(dir, _)| dir).unwrap_or_default());
+
+    let mut lines = vec![];
+    for (dir, files) in by_dir.into_iter().sorted_by_key(|(dir, _)| *dir) {
+        if !dir.is_empty() && files.len() > CHANGE_NOTE_MAX_FILES_PER_DIR {
+            lines.push(format!("{dir}/ ({} files)", files.len()));
+        } else {
+            lines.extend(files.into_iter().cloned());
+        }
+    }
+
+    if lines.len() > CHANGE_NOTE_MAX_LINES {
+        let rest = lines.len() - (CHANGE_NOTE_MAX_LINES - 1);
+        lines.truncate(CHANGE_NOTE_MAX_LINES - 1);
+        lines.push(format!("...and {rest} more"));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(files: &[(&str, &str)]) -> ContentManifest {
+        ContentManifest {
+            commit: None,
+            files: files
+                .iter()
+                .map(|(path, hash)| {
+                    (
+                        path.to_string(),
+                        ManifestEntry {
+                            size: hash.len() as u64,
+                            hash: hash.to_string(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn diff_sorts_changed_files() {
+        let old = manifest(&[("a.txt", "1"), ("b.txt", "2"), ("dir/c.txt", "3")]);
+        let new = manifest(&[("a.txt", "1"), ("b.txt", "22"), ("dir/d.txt", "4")]);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, ["dir/d.txt"]);
+        assert_eq!(diff.modified, ["b.txt"]);
+        assert_eq!(diff.removed, ["dir/c.txt"]);
+        assert!(!diff.is_empty());
+
+        assert!(new.diff(&new).is_empty());
+    }
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|it| it.to_string()).collect()
+    }
+
+    #[test]
+    fn summarize_paths_collapses_busy_dirs() {
+        assert_eq!(
+            summarize_paths(&paths(&[
+                "small/x", "dir/1", "a.txt", "dir/2", "dir/3", "dir/4", "small/y",
+            ])),
+            ["a.txt", "dir/ (4 files)", "small/x", "small/y"]
+        );
+        // Files at the root are never collapsed
+        assert_eq!(
+            summarize_paths(&paths(&["1", "2", "3", "4"])),
+            ["1", "2", "3", "4"]
+        );
+    }
+
+    #[test]
+    fn summarize_paths_caps_lines() {
+        let files = (0..20).map(|it| format!("{it:02}")).collect::<Vec<_>>();
+        let lines = summarize_paths(&files);
+        assert_eq!(lines.len(), CHANGE_NOTE_MAX_LINES);
+        assert_eq!(
+            lines[..CHANGE_NOTE_MAX_LINES - 1],
+            files[..CHANGE_NOTE_MAX_LINES - 1]
+        );
+        assert_eq!(lines.last().unwrap(), "...and 6 more");
+    }
+
+    #[test]
+    fn change_note_fills_template() {
+        let diff = ManifestDiff {
+            added: paths(&["a.txt"]),
+            modified: vec![],
+            removed: paths(&["b.txt"]),
+        };
+        assert_eq!(
+            diff.change_note(None),
+            "Added:\n- a.txt\n\nRemoved:\n- b.txt"
+        );
+        assert_eq!(
+            diff.change_note(Some(
+                "{added} new, {modified} changed, {removed} gone\n{changes}"
+            )),
+            "1 new, 0 changed, 1 gone\nAdded:\n- a.txt\n\nRemoved:\n- b.txt"
+        );
+    }
+}
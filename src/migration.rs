@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::{
+    eyre::{self, bail, Context},
+    owo_colors::OwoColorize,
+};
+use toml_edit::DocumentMut;
+use tracing::{info, warn};
+
+use crate::defines::WORKSHOP_METADATA_FILENAME;
+
+/// Current schema version of the `workshop.toml` metadata file.
+pub const WORKSHOP_SCHEMA_VERSION: u32 = 1;
+
+/// Matches the backups made of metadata files before they're migrated.
+pub const WORKSHOP_METADATA_BACKUP_GLOB: &str = "workshop.v*.bak.toml";
+
+/// Edits the document in place, so that the comments and layout of the file are kept.
+type Migration = fn(&mut DocumentMut) -> eyre::Result<()>;
+
+/// Migration at index `n` upgrades a metadata file from schema version `n` to `n + 1`.
+const MIGRATIONS: [Migration; WORKSHOP_SCHEMA_VERSION as usize] = [v0_to_v1];
+
+/// Files without a `schema_version` predate it, and are otherwise the same as v1.
+fn v0_to_v1(_document: &mut DocumentMut) -> eyre::Result<()> {
+    Ok(())
+}
+
+fn backup_filename(version: u32) -> String {
+    format!("workshop.v{version}.bak.toml")
+}
+
+/// Parses the metadata file at `path` and upgrades it to the current schema version, in memory only.
+///
+/// Returns the upgraded document along with the version it was upgraded from, or `None` if it was already up to date.
+fn migrated_document(path: &Path) -> eyre::Result<(DocumentMut, Option<u32>)> {
+    let mut document = fs_err::read_to_string(path)?
+        .parse::<DocumentMut>()
+        .with_context(|| format!("Failed to parse {:?}", path))?;
+
+    let version = match document.get("schema_version") {
+        Some(item) => match item.as_integer() {
+            Some(version) => u32::try_from(version)
+                .with_context(|| format!("Invalid schema version `{version}` in {:?}", path))?,
+            None => bail!("Invalid schema version `{}` in {:?}", item, path),
+        },
+        None => 0,
+    };
+
+    if version == WORKSHOP_SCHEMA_VERSION {
+        return Ok((document, None));
+    }
+    if version > WORKSHOP_SCHEMA_VERSION {
+        bail!(
+            "{:?} has schema version {version}, which is newer than the supported version {}. Try updating this tool.",
+            path,
+            WORKSHOP_SCHEMA_VERSION
+        );
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut document)?;
+    }
+    document.insert(
+        "schema_version",
+        toml_edit::value(i64::from(WORKSHOP_SCHEMA_VERSION)),
+    );
+
+    Ok((document, Some(version)))
+}
+
+/// Reads the metadata file at `path` as of the current schema version, without writing anything.
+///
+/// Outdated files are upgraded in memory only, with a reminder to run `migrate`, so that read-only commands and dry
+/// runs leave them untouched.
+pub fn read_workshop_metadata(path: impl AsRef<Path>) -> eyre::Result<String> {
+    let path = path.as_ref();
+    let (document, migrated_from) = migrated_document(path)?;
+    if let Some(version) = migrated_from {
+        warn!(?path, version, "Metadata file is outdated");
+        eprintln!(
+            "{}",
+            format!(
+                "[!] {:?} has schema version {version}, upgraded to {} for this run only. Run `workshop migrate` to upgrade the file.",
+                path, WORKSHOP_SCHEMA_VERSION
+            )
+            .yellow()
+        );
+    }
+
+    Ok(document.to_string())
+}
+
+/// Upgrades the metadata file at `path` in place to the current schema version, keeping a backup of the original
+/// next to it. Comments and formatting are preserved.
+///
+/// Returns the version that was migrated from, or `None` if the file was already up to date.
+pub fn migrate_workshop_metadata(path: impl AsRef<Path>) -> eyre::Result<Option<u32>> {
+    let path = path.as_ref();
+    let (document, Some(version)) = migrated_document(path)? else {
+        return Ok(None);
+    };
+
+    fs_err::copy(path, path.with_file_name(backup_filename(version)))?;
+    fs_err::write(path, document.to_string())?;
+
+    info!(
+        ?path,
+        from = version,
+        to = WORKSHOP_SCHEMA_VERSION,
+        "Migrated metadata file"
+    );
+
+    Ok(Some(version))
+}
+
+/// Migrates every `workshop.toml` found under `dir`.
+///
+/// Returns the paths of the migrated files, along with the version each was migrated from.
+pub fn migrate_workshop_metadata_in_tree(
+    dir: impl AsRef<Path>,
+) -> eyre::Result<Vec<(PathBuf, u32)>> {
+    let mut migrated = vec![];
+
+    for entry in ignore::WalkBuilder::new(dir.as_ref())
+        .standard_filters(false)
+        .filter_entry(|it| it.file_name() != ".git")
+        .build()
+    {
+        let entry = entry?;
+        if entry.file_name() == WORKSHOP_METADATA_FILENAME
+            && entry.file_type().is_some_and(|it| it.is_file())
+        {
+            if let Some(version) = migrate_workshop_metadata(entry.path())? {
+                migrated.push((entry.into_path(), version));
+            }
+        }
+    }
+
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V0_METADATA: &str = "\
+# Comments are kept
+app_id = 480
+item_id = 1 # Set by `create`
+";
+
+    fn write_metadata(contents: &str) -> eyre::Result<(tempfile::TempDir, PathBuf)> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(WORKSHOP_METADATA_FILENAME);
+        fs_err::write(&path, contents)?;
+        Ok((dir, path))
+    }
+
+    #[test]
+    fn migrate_v0_keeps_comments_and_backup() -> eyre::Result<()> {
+        let (dir, path) = write_metadata(V0_METADATA)?;
+        assert_eq!(migrate_workshop_metadata(&path)?, Some(0));
+
+        let migrated = fs_err::read_to_string(&path)?;
+        assert!(migrated.contains("# Comments are kept\n"));
+        assert!(migrated.contains("item_id = 1 # Set by `create`\n"));
+        assert!(migrated.contains("schema_version = 1"));
+        assert_eq!(
+            fs_err::read_to_string(dir.path().join(backup_filename(0)))?,
+            V0_METADATA
+        );
+
+        assert_eq!(migrate_workshop_metadata(&path)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn newer_version_is_refused() -> eyre::Result<()> {
+        let metadata = format!(
+            "schema_version = {}\napp_id = 480\n",
+            WORKSHOP_SCHEMA_VERSION + 1
+        );
+        let (dir, path) = write_metadata(&metadata)?;
+        assert!(read_workshop_metadata(&path).is_err());
+        assert!(migrate_workshop_metadata(&path).is_err());
+        assert_eq!(fs_err::read_to_string(&path)?, metadata);
+        assert_eq!(fs_err::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn read_never_writes() -> eyre::Result<()> {
+        let (dir, path) = write_metadata(V0_METADATA)?;
+        let upgraded = read_workshop_metadata(&path)?;
+        assert!(upgraded.contains("schema_version = 1"));
+        assert!(upgraded.contains("# Comments are kept\n"));
+
+        assert_eq!(fs_err::read_to_string(&path)?, V0_METADATA);
+        assert_eq!(fs_err::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::{path::PathBuf, sync::OnceLock};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use tracing::error;
+
+use crate::backend::{ItemDetails, UpdateStage};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored messages meant to be read by humans.
+    #[default]
+    Human,
+    /// JSON lines on stdout, one per event.
+    Json,
+}
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+pub fn init(format: OutputFormat) {
+    _ = OUTPUT_FORMAT.set(format);
+}
+
+pub fn is_json() -> bool {
+    OUTPUT_FORMAT.get().copied().unwrap_or_default() == OutputFormat::Json
+}
+
+/// Structured events emitted in `--output json` mode.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    StagingStarted {
+        content_path: PathBuf,
+    },
+    StagingFinished {
+        file_count: usize,
+        total_bytes: u64,
+    },
+    ItemCreated {
+        item_id: u64,
+        app_id: u32,
+    },
+    Progress {
+        item_id: u64,
+        stage: UpdateStage,
+        bytes_processed: u64,
+        bytes_total: u64,
+    },
+    /// The content matches the last upload, so only the metadata is updated.
+    ContentUnchanged {
+        item_id: u64,
+    },
+    UpdateSubmitted {
+        item_id: u64,
+    },
+    LegalAgreementRequired {
+        item_id: u64,
+    },
+    ItemDeleted {
+        item_id: u64,
+    },
+    MetadataPulled {
+        item_id: u64,
+    },
+    Migrated {
+        path: PathBuf,
+        from_version: u32,
+        to_version: u32,
+    },
+    Item(ItemDetails),
+    File {
+        path: String,
+        size: u64,
+    },
+    Excluded {
+        path: String,
+        is_dir: bool,
+        rule: String,
+    },
+    Rendered {
+        description: String,
+    },
+    Error {
+        message: String,
+        code: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hint: Option<String>,
+    },
+}
+
+/// Prints the event as a JSON line on stdout in `--output json` mode, does nothing otherwise.
+pub fn emit(event: Event) {
+    if !is_json() {
+        return;
+    }
+
+    match serde_json::to_string(&event) {
+        Ok(line) => println!("{line}"),
+        Err(err) => error!(%err, ?event, "Failed to serialize event"),
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    ffi::OsStr,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{self, bail, Context, ContextCompat},
+    owo_colors::OwoColorize,
+};
+use fs_err::PathExt;
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType};
+use itertools::Itertools;
+use tracing::{info, warn};
+
+use crate::{
+    backend::{AdditionalPreview, PreviewChange, PreviewSource},
+    cli::WorkshopItemArgs,
+    config::PreviewConfig,
+    workshop::{format_size, is_valid_preview_type},
+};
+
+/// Max size of a preview image. Steam rejects larger ones, and they take up the user's Cloud quota regardless.
+///
+/// https://partner.steamgames.com/doc/features/workshop/implementation#preview_image
+pub const PREVIEW_MAX_BYTES: u64 = 1024 * 1024;
+/// Longest side of a preview image beyond which it only wastes space, as the Workshop never shows it any larger.
+pub const PREVIEW_MAX_DIMENSION: u32 = 1920;
+/// Width to height ratios recommended for previews, as others may get cropped in Workshop listings.
+const PREVIEW_RECOMMENDED_ASPECT_RATIOS: [(u32, u32); 2] = [(16, 9), (1, 1)];
+
+/// Checks the type, size and dimensions of a preview image.
+///
+/// Returns the path to upload, which is a downscaled JPEG copy in `convert_dir` if the image is too large and
+/// `convert_dir` is given.
+pub fn prepare_preview_image(path: &Path, convert_dir: Option<&Path>) -> eyre::Result<PathBuf> {
+    is_valid_preview_type(path)?;
+
+    let size = fs_err::metadata(path)?.len();
+    let (width, height) = image::image_dimensions(path)
+        .with_context(|| format!("Failed to read the dimensions of preview {:?}", path))?;
+
+    let aspect_ratio = width as f64 / height as f64;
+    if !PREVIEW_RECOMMENDED_ASPECT_RATIOS
+        .iter()
+        .any(|(w, h)| (aspect_ratio - *w as f64 / *h as f64).abs() < 0.02)
+    {
+        warn!(
+            ?path,
+            width, height, "Preview has a non-recommended aspect ratio"
+        );
+        eprintln!(
+            "{}",
+            format!(
+                "[!] Preview {:?} is {width}x{height}. An aspect ratio of 16:9 or 1:1 is recommended, others may get cropped in Workshop listings.",
+                path
+            )
+            .yellow()
+        );
+    }
+
+    let too_large = width.max(height) > PREVIEW_MAX_DIMENSION;
+    match convert_dir {
+        Some(convert_dir) if size > PREVIEW_MAX_BYTES || too_large => {
+            convert_preview_image(path, convert_dir)
+        }
+        _ if size > PREVIEW_MAX_BYTES => bail!(
+            "Preview {:?} is {}, over Steam's limit of {}. Pass `--preview-auto-convert` to upload a smaller JPEG copy instead",
+            path,
+            format_size(size),
+            format_size(PREVIEW_MAX_BYTES)
+        ),
+        _ => {
+            if too_large {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "[!] Preview {:?} is {width}x{height}, larger than the {PREVIEW_MAX_DIMENSION}px Steam shows.",
+                        path
+                    )
+                    .yellow()
+                );
+            }
+            Ok(path.to_path_buf())
+        }
+    }
+}
+
+/// Re-encodes the image as a JPEG that fits within [`PREVIEW_MAX_DIMENSION`] and [`PREVIEW_MAX_BYTES`], lowering the
+/// quality and then the resolution until it does.
+fn convert_preview_image(path: &Path, convert_dir: &Path) -> eyre::Result<PathBuf> {
+    if infer::get_from_path(path)?.is_some_and(|it| it.mime_type() == "image/gif") {
+        eprintln!(
+            "{}",
+            format!(
+                "[!] Only the first frame of animated preview {:?} is kept when converting.",
+                path
+            )
+            .yellow()
+        );
+    }
+
+    // JPEG has no alpha channel
+    let mut image = image::open(path)
+        .with_context(|| format!("Failed to decode preview {:?}", path))?
+        .to_rgb8();
+    if image.width().max(image.height()) > PREVIEW_MAX_DIMENSION {
+        let scale = PREVIEW_MAX_DIMENSION as f64 / image.width().max(image.height()) as f64;
+        image = image::imageops::resize(
+            &image,
+            (image.width() as f64 * scale) as u32,
+            (image.height() as f64 * scale) as u32,
+            FilterType::Lanczos3,
+        );
+    }
+
+    let mut quality = 90;
+    let bytes = loop {
+        let mut bytes = vec![];
+        JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(&image)?;
+        if bytes.len() as u64 <= PREVIEW_MAX_BYTES {
+            break bytes;
+        }
+
+        if quality > 60 {
+            quality -= 10;
+        } else if image.width().min(image.height()) > 64 {
+            image = image::imageops::resize(
+                &image,
+                image.width() * 3 / 4,
+                image.height() * 3 / 4,
+                FilterType::Lanczos3,
+            );
+        } else {
+            bail!(
+                "Failed to convert preview {:?} to a small enough JPEG",
+                path
+            );
+        }
+    };
+
+    // Previews are told apart by file name, so the stem is kept unless another preview already took it
+    let stem = path
+        .file_stem()
+        .context("Preview path has no file name")?
+        .to_string_lossy();
+    let (converted_path, mut file) = (1..)
+        .map(|it| match it {
+            1 => convert_dir.join(format!("{stem}.jpg")),
+            _ => convert_dir.join(format!("{stem}-{it}.jpg")),
+        })
+        .find_map(|it| {
+            match fs_err::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&it)
+            {
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => None,
+                file => Some(file.map(|file| (it, file))),
+            }
+        })
+        .expect("Ran out of preview file names")?;
+    file.write_all(&bytes)?;
+
+    info!(?path, ?converted_path, quality, "Converted preview");
+    eprintln!(
+        "{}",
+        format!(
+            "[+] Converted preview {:?} to a {}x{} JPEG of {}.",
+            path,
+            image.width(),
+            image.height(),
+            format_size(bytes.len() as u64)
+        )
+        .green()
+    );
+
+    Ok(converted_path)
+}
+
+pub fn check_youtube_video_id(video_id: &str) -> eyre::Result<()> {
+    if video_id.len() != 11
+        || !video_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("`{video_id}` is not a YouTube video id, which is the 11 characters after `watch?v=` in its URL");
+    }
+    Ok(())
+}
+
+/// Whether `preview` is the one referred to by `name`, i.e. its file name, its path as given or its YouTube video
+/// id.
+fn preview_matches(preview: &PreviewConfig, name: &str) -> bool {
+    match preview {
+        PreviewConfig::Image(path) => {
+            path == Path::new(name) || path.file_name() == Some(OsStr::new(name))
+        }
+        PreviewConfig::YouTube(video_id) => video_id == name,
+    }
+}
+
+/// The configured previews with the ones added and removed through `workshop_item` applied.
+///
+/// Added images have to be inside `content_path`, and are made relative to it so that `workshop.toml` stays portable.
+pub fn resolve_previews(
+    configured: &[PreviewConfig],
+    workshop_item: &WorkshopItemArgs,
+    content_path: impl AsRef<Path>,
+) -> eyre::Result<Vec<PreviewConfig>> {
+    let content_path = content_path.as_ref().fs_err_canonicalize()?;
+
+    let mut previews = configured.to_vec();
+    for image in &workshop_item.add_preview_images {
+        let image = image.fs_err_canonicalize()?;
+        let Ok(relative) = image.strip_prefix(&content_path) else {
+            bail!(
+                "Preview {:?} is not inside the content folder {:?}. Move it there first",
+                image,
+                content_path
+            );
+        };
+        previews.push(PreviewConfig::Image(relative.to_path_buf()));
+    }
+    for video_id in &workshop_item.add_preview_videos {
+        check_youtube_video_id(video_id)?;
+        previews.push(PreviewConfig::YouTube(video_id.clone()));
+    }
+    for removed in &workshop_item.removed_previews {
+        let count = previews.len();
+        previews.retain(|it| !preview_matches(it, removed));
+        if previews.len() == count {
+            bail!("No additional preview matches `{removed}`");
+        }
+    }
+
+    Ok(previews)
+}
+
+/// Changes turning the `remote` previews into the `desired` ones.
+///
+/// Previews are replaced in place for as long as both sides have the same kind at the same position. Past that, the
+/// remaining remote ones are removed and the remaining desired ones added. Images are compared by file name only.
+///
+/// Remote previews of other kinds are never touched, and left out when matching positions.
+///
+/// Images are prepared with [`prepare_preview_image`].
+pub fn diff_previews(
+    desired: &[PreviewConfig],
+    remote: &[AdditionalPreview],
+    content_path: impl AsRef<Path>,
+    convert_dir: Option<&Path>,
+) -> eyre::Result<Vec<PreviewChange>> {
+    let sources = desired
+        .iter()
+        .map(|it| {
+            Ok(match it {
+                PreviewConfig::Image(path) => {
+                    let path =
+                        prepare_preview_image(&content_path.as_ref().join(path), convert_dir)?;
+                    PreviewSource::Image(path.fs_err_canonicalize()?)
+                }
+                PreviewConfig::YouTube(video_id) => {
+                    check_youtube_video_id(video_id)?;
+                    PreviewSource::YouTube(video_id.clone())
+                }
+            })
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let remote = remote
+        .iter()
+        .enumerate()
+        .filter(|(_, it)| **it != AdditionalPreview::Other)
+        .collect_vec();
+
+    let mut changes = vec![];
+    let mut index = 0;
+    while index < sources.len().min(remote.len()) {
+        let (remote_index, remote_preview) = remote[index];
+        let unchanged = match (&sources[index], remote_preview) {
+            (PreviewSource::Image(path), AdditionalPreview::Image { filename, .. }) => {
+                path.file_name() == Some(OsStr::new(filename))
+            }
+            (PreviewSource::YouTube(video_id), AdditionalPreview::YouTube { video_id: remote }) => {
+                video_id == remote
+            }
+            _ => break,
+        };
+        if !unchanged {
+            changes.push(PreviewChange::Replace {
+                index: remote_index as u32,
+                source: sources[index].clone(),
+            });
+        }
+        index += 1;
+    }
+    changes.extend(
+        remote[index..]
+            .iter()
+            .map(|(remote_index, _)| PreviewChange::Remove {
+                index: *remote_index as u32,
+            }),
+    );
+    changes.extend(sources.into_iter().skip(index).map(PreviewChange::Add));
+
+    Ok(changes)
+}
@@ -0,0 +1,117 @@
+use std::{
+    io::{self, IsTerminal},
+    time::{Duration, Instant},
+};
+
+use color_eyre::owo_colors::OwoColorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use tracing::info;
+
+use crate::{
+    backend::{UpdateProgress, UpdateStage},
+    output::{self, Event},
+    workshop::format_size,
+};
+
+/// How often progress is logged when stderr isn't a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// How often progress events are emitted in `--output json` mode.
+const EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reports the progress of an item submission, as a progress bar on a terminal or as periodic log lines otherwise.
+/// In `--output json` mode, progress events are emitted instead.
+pub struct UploadProgressReporter {
+    item_id: u64,
+    bar: Option<ProgressBar>,
+    stage: UpdateStage,
+    last_logged: Instant,
+}
+
+impl UploadProgressReporter {
+    pub fn new(item_id: u64) -> Self {
+        let bar = (!output::is_json() && io::stderr().is_terminal()).then(|| {
+            let bar = ProgressBar::new(0);
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{spinner:.cyan} {msg:20} [{bar:30.cyan/blue}] {bytes}/{total_bytes} ({eta})",
+                )
+                .expect("Template is valid")
+                .progress_chars("=> "),
+            );
+            bar.enable_steady_tick(Duration::from_millis(100));
+            bar
+        });
+
+        Self {
+            item_id,
+            bar,
+            stage: UpdateStage::Invalid,
+            last_logged: Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, progress: UpdateProgress) {
+        // Steam reports this before the submission starts and after it's done
+        if progress.stage == UpdateStage::Invalid {
+            return;
+        }
+
+        let stage_changed = progress.stage != self.stage;
+        self.stage = progress.stage;
+
+        if stage_changed {
+            info!(
+                stage = %progress.stage,
+                bytes_processed = progress.bytes_processed,
+                bytes_total = progress.bytes_total,
+                "Item update progress"
+            );
+        }
+
+        if output::is_json() {
+            if stage_changed || self.last_logged.elapsed() >= EVENT_INTERVAL {
+                self.last_logged = Instant::now();
+                output::emit(Event::Progress {
+                    item_id: self.item_id,
+                    stage: progress.stage,
+                    bytes_processed: progress.bytes_processed,
+                    bytes_total: progress.bytes_total,
+                });
+            }
+            return;
+        }
+
+        match &self.bar {
+            Some(bar) => {
+                if stage_changed {
+                    bar.set_message(progress.stage.to_string());
+                }
+                bar.set_length(progress.bytes_total);
+                bar.set_position(progress.bytes_processed);
+            }
+            None => {
+                if stage_changed || self.last_logged.elapsed() >= LOG_INTERVAL {
+                    self.last_logged = Instant::now();
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "[-] {}... {}/{}",
+                            progress.stage,
+                            format_size(progress.bytes_processed),
+                            format_size(progress.bytes_total)
+                        )
+                        .cyan()
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Drop for UploadProgressReporter {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
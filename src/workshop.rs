@@ -1,17 +1,34 @@
-use std::{borrow::Cow, fmt, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use color_eyre::eyre::{self, bail, ContextCompat};
+use color_eyre::{
+    eyre::{self, bail, ContextCompat},
+    owo_colors::OwoColorize,
+};
 use fs_err::PathExt;
 use itertools::Itertools;
-use relative_path::PathExt as RelPathExt;
+use relative_path::{PathExt as RelPathExt, RelativePathBuf};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use tracing::{debug, info, warn};
 
 use crate::{
-    config::{Config, WorkshopItemConfig},
-    defines::WORKSHOP_METADATA_FILENAME,
-    ext::{SteamworksClient, SteamworksSingleClient, UGCBlockingExt},
+    backend::{ItemDetails, ItemUpdate, PreviewChange, WorkshopBackend},
+    bbcode::markdown_to_bbcode,
+    cli::{DescriptionFormat, WorkshopItemArgs},
+    config::{Config, LocalizationConfig, PreviewConfig, WorkshopItemConfig},
+    defines::{
+        WORKSHOP_LOCALIZATION_DIR, WORKSHOP_MANIFEST_FILENAME, WORKSHOP_METADATA_ARCHIVE_FILENAME,
+        WORKSHOP_METADATA_FILENAME,
+    },
+    ext::{SteamworksClient, SteamworksSingleClient},
+    migration::WORKSHOP_METADATA_BACKUP_GLOB,
+    output,
 };
 
 #[serde_as]
@@ -94,6 +111,44 @@ impl AsRef<str> for Tag {
     }
 }
 
+/// Max size of an item's metadata string, in bytes.
+///
+/// https://partner.steamgames.com/doc/api/ISteamRemoteStorage#k_cchDeveloperMetadataMax
+pub const METADATA_MAX_BYTES: usize = 5000;
+
+pub fn check_metadata(metadata: &str) -> eyre::Result<()> {
+    if metadata.len() > METADATA_MAX_BYTES {
+        bail!(
+            "Item metadata is {} bytes, over Steam's limit of {METADATA_MAX_BYTES} bytes",
+            metadata.len()
+        );
+    }
+    Ok(())
+}
+
+/// https://partner.steamgames.com/doc/api/ISteamUGC#AddItemKeyValueTag
+pub fn check_key_value_tag(key: &str, value: &str) -> eyre::Result<()> {
+    if key.is_empty() {
+        bail!("Empty key-value tag keys are not allowed");
+    }
+    if key.len() > 255 || value.len() > 255 {
+        bail!("Key-value tag `{key}` can only have a key and value of up to 255 characters each");
+    }
+    if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("Key-value tag key `{key}` can only contain letters, numbers and underscores");
+    }
+    Ok(())
+}
+
+/// Parses a `key=value` pair.
+pub fn parse_key_value_tag(s: &str) -> eyre::Result<(String, String)> {
+    let (key, value) = s
+        .split_once('=')
+        .with_context(|| format!("Expected `key=value`, got `{s}`"))?;
+    check_key_value_tag(key, value)?;
+    Ok((key.to_owned(), value.to_owned()))
+}
+
 pub fn is_valid_preview_type(path: impl AsRef<Path>) -> eyre::Result<()> {
     match infer::get_from_path(path)?
         .context("Unknown file type")?
@@ -124,28 +179,39 @@ pub fn steamworks_client_init(
     })?)
 }
 
-/// Both `from` and `to` are paths to directory.
-/// Make a copy of data in `from` in `to` while ignoring files matched in the glob.
-pub fn copy_filtered_content<I, O>(
-    from: I,
-    to: O,
+/// A file copied over to the staging directory, relative to the content root.
+#[derive(Debug, Clone)]
+pub struct StagedFile {
+    pub path: RelativePathBuf,
+    pub size: u64,
+}
+
+/// Override globs applied to the content folder, i.e. the ones excluding this tool's own files followed by `globs`.
+fn content_override_globs(globs: Option<&[impl AsRef<str>]>) -> Vec<String> {
+    [
+        format!("!{}", WORKSHOP_METADATA_FILENAME),
+        format!("!{}", WORKSHOP_METADATA_ARCHIVE_FILENAME),
+        format!("!{}", WORKSHOP_MANIFEST_FILENAME),
+        format!("!{}", WORKSHOP_METADATA_BACKUP_GLOB),
+        format!("!/{}/", WORKSHOP_LOCALIZATION_DIR),
+    ]
+    .into_iter()
+    .chain(globs.into_iter().flatten().map(|it| it.as_ref().to_owned()))
+    .collect()
+}
+
+/// The walker deciding which files from the content folder `from` make it into the item.
+fn content_walk_builder(
+    from: &Path,
     globs: Option<&[impl AsRef<str>]>,
     ignore_files: Option<&[impl AsRef<Path>]>,
-) -> eyre::Result<()>
-where
-    I: AsRef<Path>,
-    O: AsRef<Path>,
-{
-    let mut overrides = ignore::overrides::OverrideBuilder::new(from.as_ref());
-    overrides.add(&format!("!{}", WORKSHOP_METADATA_FILENAME))?;
-
-    if let Some(globs) = globs {
-        for glob in globs {
-            overrides.add(glob.as_ref())?;
-        }
+) -> eyre::Result<ignore::WalkBuilder> {
+    let mut overrides = ignore::overrides::OverrideBuilder::new(from);
+    for glob in content_override_globs(globs) {
+        overrides.add(&glob)?;
     }
 
-    let mut walk_builder = ignore::WalkBuilder::new(from.as_ref());
+    let mut walk_builder = ignore::WalkBuilder::new(from);
     walk_builder.overrides(overrides.build()?);
 
     if let Some(ignore_files) = ignore_files {
@@ -154,7 +220,25 @@ where
         }
     }
 
-    for entry in walk_builder
+    Ok(walk_builder)
+}
+
+/// Both `from` and `to` are paths to directory.
+/// Make a copy of data in `from` in `to` while ignoring files matched in the glob.
+///
+/// Returns the files that were copied.
+pub fn copy_filtered_content<I, O>(
+    from: I,
+    to: O,
+    globs: Option<&[impl AsRef<str>]>,
+    ignore_files: Option<&[impl AsRef<Path>]>,
+) -> eyre::Result<Vec<StagedFile>>
+where
+    I: AsRef<Path>,
+    O: AsRef<Path>,
+{
+    let mut staged_files = vec![];
+    for entry in content_walk_builder(from.as_ref(), globs, ignore_files)?
         .build()
         .inspect(|it| {
             _ = it.as_ref().inspect_err(|err| warn!("{err}"));
@@ -170,39 +254,815 @@ where
                 fs_err::create_dir_all(proxy_path)?;
             } else if file_type.is_file() {
                 debug!(file = %relative_entry_path, "Adding to item content");
-                fs_err::copy(entry.path().fs_err_canonicalize()?, &proxy_path)?;
+                let size = fs_err::copy(entry.path().fs_err_canonicalize()?, &proxy_path)?;
+                staged_files.push(StagedFile {
+                    path: relative_entry_path,
+                    size,
+                });
             }
         }
     }
 
-    Ok(())
+    Ok(staged_files)
+}
+
+/// Why a file or directory in the content folder doesn't make it into the item.
+#[derive(Debug, Clone)]
+pub enum ExclusionRule {
+    /// Matched by an exclusion glob, either from `--glob` or one of the tool's own.
+    Glob(String),
+    /// `--glob` whitelists were passed, but none matched.
+    NotWhitelisted,
+    /// Matched by a pattern from a file passed with `--ignore-file`.
+    IgnoreFile {
+        file: PathBuf,
+        pattern: String,
+    },
+    /// Matched by a pattern from a `.gitignore` or `.ignore` file in the content folder.
+    Gitignore {
+        file: PathBuf,
+        pattern: String,
+    },
+    Hidden,
+    /// Excluded by rules from outside the content folder, such as a global gitignore.
+    Other,
+}
+
+impl fmt::Display for ExclusionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExclusionRule::Glob(glob) => write!(f, "glob `{glob}`"),
+            ExclusionRule::NotWhitelisted => write!(f, "not matched by any whitelist glob"),
+            ExclusionRule::IgnoreFile { file, pattern } => {
+                write!(f, "ignore file {:?}: `{pattern}`", file)
+            }
+            ExclusionRule::Gitignore { file, pattern } => {
+                write!(f, "gitignore {:?}: `{pattern}`", file)
+            }
+            ExclusionRule::Hidden => write!(f, "hidden"),
+            ExclusionRule::Other => write!(f, "other ignore rules"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExcludedEntry {
+    pub path: RelativePathBuf,
+    pub is_dir: bool,
+    pub rule: ExclusionRule,
+}
+
+/// Works out which rule excluded `path`, roughly in the same order of precedence as the walker applies them.
+fn exclusion_rule(
+    from: &Path,
+    path: &Path,
+    is_dir: bool,
+    overrides: &ignore::overrides::Override,
+    exclusion_globs: &[(String, ignore::overrides::Override)],
+    ignore_files: &[ignore::gitignore::Gitignore],
+    gitignores: &mut HashMap<PathBuf, Vec<ignore::gitignore::Gitignore>>,
+) -> ExclusionRule {
+    if overrides.matched(path, is_dir).is_ignore() {
+        return exclusion_globs
+            .iter()
+            .find(|(_, it)| it.matched(path, is_dir).is_ignore())
+            .map(|(glob, _)| ExclusionRule::Glob(glob.clone()))
+            .unwrap_or(ExclusionRule::NotWhitelisted);
+    }
+
+    // Ignore files closer to the path take precedence
+    'gitignores: for dir in path.ancestors().skip(1) {
+        let dir_gitignores = gitignores.entry(dir.to_path_buf()).or_insert_with(|| {
+            [".ignore", ".gitignore"]
+                .into_iter()
+                .map(|it| dir.join(it))
+                .filter(|it| it.is_file())
+                .map(|it| ignore::gitignore::Gitignore::new(it).0)
+                .collect()
+        });
+        for gitignore in dir_gitignores.iter() {
+            match gitignore.matched(path, is_dir) {
+                ignore::Match::Ignore(glob) => {
+                    return ExclusionRule::Gitignore {
+                        file: glob.from().map(Path::to_path_buf).unwrap_or_default(),
+                        pattern: glob.original().to_owned(),
+                    }
+                }
+                ignore::Match::Whitelist(_) => break 'gitignores,
+                ignore::Match::None => {}
+            }
+        }
+        if dir == from {
+            break;
+        }
+    }
+
+    for ignore_file in ignore_files {
+        if let ignore::Match::Ignore(glob) = ignore_file.matched(path, is_dir) {
+            return ExclusionRule::IgnoreFile {
+                file: glob.from().map(Path::to_path_buf).unwrap_or_default(),
+                pattern: glob.original().to_owned(),
+            };
+        }
+    }
+
+    if path
+        .file_name()
+        .is_some_and(|it| it.to_string_lossy().starts_with('.'))
+    {
+        return ExclusionRule::Hidden;
+    }
+
+    ExclusionRule::Other
+}
+
+/// Lists the files from `from` that would be staged by [`copy_filtered_content`], along with the topmost entries that
+/// were excluded and why.
+pub fn list_content_files(
+    from: impl AsRef<Path>,
+    globs: Option<&[impl AsRef<str>]>,
+    ignore_files: Option<&[impl AsRef<Path>]>,
+) -> eyre::Result<(Vec<StagedFile>, Vec<ExcludedEntry>)> {
+    let from = from.as_ref();
+
+    let mut included_paths = HashSet::new();
+    let mut included = vec![];
+    for entry in content_walk_builder(from, globs, ignore_files)?
+        .build()
+        .inspect(|it| {
+            _ = it.as_ref().inspect_err(|err| warn!("{err}"));
+        })
+        .filter_map(|it| it.ok())
+    {
+        if entry.depth() != 0 && entry.file_type().is_some_and(|it| it.is_file()) {
+            included.push(StagedFile {
+                path: entry.path().relative_to(from)?,
+                size: entry.metadata()?.len(),
+            });
+        }
+        included_paths.insert(entry.into_path());
+    }
+
+    let mut overrides = ignore::overrides::OverrideBuilder::new(from);
+    let mut exclusion_globs = vec![];
+    for glob in content_override_globs(globs) {
+        overrides.add(&glob)?;
+        if glob.starts_with('!') {
+            let single = ignore::overrides::OverrideBuilder::new(from)
+                .add(&glob)?
+                .build()?;
+            exclusion_globs.push((glob, single));
+        }
+    }
+    let overrides = overrides.build()?;
+    let ignore_files = ignore_files
+        .into_iter()
+        .flatten()
+        .map(|it| {
+            let mut builder = ignore::gitignore::GitignoreBuilder::new(from);
+            if let Some(err) = builder.add(it.as_ref()) {
+                warn!("{err}");
+            }
+            Ok(builder.build()?)
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let mut gitignores = HashMap::new();
+
+    // Only descending into included directories, so that just the topmost excluded entries are reported
+    let included_paths = Arc::new(included_paths);
+    let mut excluded = vec![];
+    for entry in ignore::WalkBuilder::new(from)
+        .standard_filters(false)
+        .filter_entry({
+            let included_paths = Arc::clone(&included_paths);
+            move |it| {
+                it.depth() == 0
+                    || it
+                        .path()
+                        .parent()
+                        .is_some_and(|it| included_paths.contains(it))
+            }
+        })
+        .build()
+        .filter_map(|it| it.ok())
+    {
+        if included_paths.contains(entry.path()) {
+            continue;
+        }
+
+        let is_dir = entry.file_type().is_some_and(|it| it.is_dir());
+        excluded.push(ExcludedEntry {
+            path: entry.path().relative_to(from)?,
+            is_dir,
+            rule: exclusion_rule(
+                from,
+                entry.path(),
+                is_dir,
+                &overrides,
+                &exclusion_globs,
+                &ignore_files,
+                &mut gitignores,
+            ),
+        });
+    }
+
+    Ok((included, excluded))
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for it in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = it;
+    }
+
+    format!("{size:.1} {unit}")
+}
+
+/// Prints what submitting `update` would change on the `remote` item, which is `None` for items yet to be created.
+///
+/// Goes to stderr in `--output json` mode, to keep stdout to JSON lines.
+pub fn print_update_plan(
+    update: &ItemUpdate,
+    localized_updates: &[ItemUpdate],
+    remote: Option<&ItemDetails>,
+    staged_files: Option<&[StagedFile]>,
+) {
+    macro_rules! print_line {
+        ($($arg:tt)*) => {
+            if output::is_json() {
+                eprintln!($($arg)*)
+            } else {
+                println!($($arg)*)
+            }
+        };
+    }
+
+    fn print_change(field: &str, old: Option<String>, new: Option<String>) {
+        match (old, new) {
+            (_, None) => print_line!("  {field}: {}", "unchanged".dimmed()),
+            (Some(old), Some(new)) if old == new => {
+                print_line!("  {field}: {}", "unchanged".dimmed())
+            }
+            (Some(old), Some(new)) => print_line!("  {field}: {:?} -> {:?}", old, new.green()),
+            (None, Some(new)) => print_line!("  {field}: {:?}", new.green()),
+        }
+    }
+
+    match remote {
+        Some(remote) => print_line!("{}", format!("Plan for item {}:", remote.item_id).bold()),
+        None => print_line!("{}", "Plan for a new item:".bold()),
+    }
+
+    print_change(
+        "title",
+        remote.map(|it| it.title.clone()),
+        update.title.clone(),
+    );
+    match (remote.map(|it| &it.description), &update.description) {
+        (Some(old), Some(new)) if old != new => print_line!(
+            "  description: {} ({} -> {} characters)",
+            "changed".green(),
+            old.chars().count(),
+            new.chars().count()
+        ),
+        (None, Some(new)) => print_line!("  description: {} characters", new.chars().count()),
+        _ => print_line!("  description: {}", "unchanged".dimmed()),
+    }
+    print_change(
+        "visibility",
+        remote.map(|it| it.visibility.to_string()),
+        update.visibility.map(|it| it.to_string()),
+    );
+    print_change(
+        "tags",
+        remote.map(|it| it.tags.iter().join(", ")),
+        update.tags.as_ref().map(|it| it.iter().join(", ")),
+    );
+    for localized in localized_updates {
+        print_line!(
+            "  {} localization: title {}, description {}",
+            localized.language.as_deref().unwrap_or_default(),
+            localized
+                .title
+                .as_ref()
+                .map_or("unchanged".dimmed().to_string(), |it| format!(
+                    "{:?}",
+                    it.green()
+                )),
+            localized
+                .description
+                .as_ref()
+                .map_or("unchanged".dimmed().to_string(), |it| format!(
+                    "{} characters",
+                    it.chars().count()
+                )),
+        );
+    }
+    for (key, value) in &update.key_value_tags {
+        print_line!("  key-value tag: {key} = {:?}", value.green());
+    }
+    for key in &update.removed_key_value_tags {
+        print_line!("  key-value tag: {key} {}", "removed".red());
+    }
+    match &update.metadata {
+        Some(metadata) => print_line!("  metadata: {} bytes", metadata.len()),
+        None => print_line!("  metadata: {}", "unchanged".dimmed()),
+    }
+    match &update.preview_path {
+        Some(preview_path) => print_line!("  preview: {:?} will be uploaded", preview_path),
+        None => print_line!("  preview: {}", "unchanged".dimmed()),
+    }
+    if update.preview_changes.is_empty() {
+        print_line!("  additional previews: {}", "unchanged".dimmed());
+    }
+    for change in &update.preview_changes {
+        match change {
+            PreviewChange::Add(source) => {
+                print_line!("  additional preview: {} {}", source, "added".green())
+            }
+            PreviewChange::Replace { index, source } => print_line!(
+                "  additional preview #{index}: {} {}",
+                "replaced with".green(),
+                source
+            ),
+            PreviewChange::Remove { index } => {
+                print_line!("  additional preview #{index}: {}", "removed".red())
+            }
+        }
+    }
+    match &update.change_note {
+        Some(change_note) => print_line!("  change note: {:?}", change_note),
+        None => print_line!("  change note: {}", "none".dimmed()),
+    }
+
+    match staged_files {
+        Some(staged_files) => {
+            print_line!(
+                "  content: {} files, {}",
+                staged_files.len(),
+                format_size(staged_files.iter().map(|it| it.size).sum())
+            );
+            for file in staged_files {
+                print_line!("    {} ({})", file.path, format_size(file.size));
+            }
+        }
+        None => print_line!("  content: {}", "unchanged".dimmed()),
+    }
 }
 
+/// The metadata file records the item's tags, key-value tags, title, description, visibility and metadata string, as
+/// well as the preview, description and metadata file if they're inside the content directory, and the additional
+/// `previews`.
 pub fn create_item_with_metadata_file(
-    client: &SteamworksClient,
-    single: &SteamworksSingleClient,
-    app_id: impl Into<steamworks::AppId>,
+    backend: &dyn WorkshopBackend,
+    app_id: AppId,
     content_path: impl AsRef<Path>,
-    tags: &[Tag],
+    workshop_item: &WorkshopItemArgs,
+    previews: &[PreviewConfig],
 ) -> eyre::Result<(steamworks::PublishedFileId, bool)> {
-    let app_id = app_id.into();
-    let (file_id, agreement) =
-        client
-            .ugc()
-            .create_item_blocking(single, app_id, steamworks::FileType::Community)?;
+    let (file_id, agreement) = backend.create_item(app_id)?;
 
     info!(item_id = file_id.0, "Workshop item created");
 
+    let relative_to_content = |path: &Option<PathBuf>| -> eyre::Result<Option<PathBuf>> {
+        Ok(match path {
+            Some(path) => path
+                .fs_err_canonicalize()?
+                .strip_prefix(content_path.as_ref().fs_err_canonicalize()?)
+                .ok()
+                .map(Path::to_path_buf),
+            None => None,
+        })
+    };
+    let preview = relative_to_content(&workshop_item.preview_path)?;
+    let metadata_file = relative_to_content(&workshop_item.metadata_file)?;
+    let description_file = relative_to_content(&workshop_item.description_file)?;
+
     _ = WorkshopItemConfig {
         app_id: app_id.0,
         item_id: file_id.0,
-        tags: tags.to_owned(),
+        tags: workshop_item.tags.clone(),
+        title: workshop_item.title.clone(),
+        // Otherwise, the description has already been converted from its format
+        description: description_file
+            .is_none()
+            .then(|| workshop_item.description.clone())
+            .flatten(),
+        description_format: description_file
+            .is_some()
+            .then_some(workshop_item.description_format)
+            .flatten(),
+        description_file,
+        visibility: workshop_item.visibility,
+        preview,
+        previews: previews.to_vec(),
+        metadata: metadata_file
+            .is_none()
+            .then(|| workshop_item.metadata.clone())
+            .flatten(),
+        metadata_file,
+        key_value_tags: workshop_item.key_value_tags.iter().cloned().collect(),
+        ..Default::default()
     }
     .store_path(content_path.as_ref().join(WORKSHOP_METADATA_FILENAME))?;
 
     Ok((file_id, agreement))
 }
 
+/// Fills in whatever wasn't passed as cli args from the item's metadata file.
+///
+/// Cli args take precedence over `workshop.toml`, which in turn takes precedence over the remote item. A
+/// `description_file` takes precedence over an inline `description`.
+pub fn apply_item_config(
+    workshop_item: &mut WorkshopItemArgs,
+    workshop_item_cfg: &WorkshopItemConfig,
+    content_path: impl AsRef<Path>,
+) -> eyre::Result<()> {
+    if workshop_item.tags.is_empty() {
+        workshop_item
+            .tags
+            .extend_from_slice(&workshop_item_cfg.tags);
+    }
+    if workshop_item.title.is_none() {
+        workshop_item.title = workshop_item_cfg.title.clone();
+    }
+    if workshop_item.description.is_none() && workshop_item.description_file.is_none() {
+        workshop_item.description = match &workshop_item_cfg.description_file {
+            Some(description_file) => Some(fs_err::read_to_string(
+                content_path.as_ref().join(description_file),
+            )?),
+            None => workshop_item_cfg.description.clone(),
+        };
+    }
+    if workshop_item.description_format.is_none() {
+        workshop_item.description_format = workshop_item_cfg.description_format;
+    }
+    if workshop_item.visibility.is_none() {
+        workshop_item.visibility = workshop_item_cfg.visibility;
+    }
+    if workshop_item.preview_path.is_none() {
+        workshop_item.preview_path = workshop_item_cfg
+            .preview
+            .as_ref()
+            .map(|it| content_path.as_ref().join(it));
+    }
+    if workshop_item.metadata.is_none() && workshop_item.metadata_file.is_none() {
+        workshop_item.metadata = match &workshop_item_cfg.metadata_file {
+            Some(metadata_file) => Some(fs_err::read_to_string(
+                content_path.as_ref().join(metadata_file),
+            )?),
+            None => workshop_item_cfg.metadata.clone(),
+        };
+    }
+    // Cli args override the values of the same keys
+    let cli_key_value_tags = std::mem::take(&mut workshop_item.key_value_tags);
+    workshop_item.key_value_tags = workshop_item_cfg
+        .key_value_tags
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .filter(|(key, _)| {
+            !cli_key_value_tags.iter().any(|(it, _)| it == key)
+                && !workshop_item.removed_key_value_tags.contains(key)
+        })
+        .chain(cli_key_value_tags)
+        .collect();
+    for (key, value) in &workshop_item.key_value_tags {
+        check_key_value_tag(key, value)?;
+    }
+
+    Ok(())
+}
+
+/// Language codes accepted by the Steam API.
+///
+/// https://partner.steamgames.com/doc/store/localization/languages
+pub const STEAM_LANGUAGES: [&str; 30] = [
+    "arabic",
+    "bulgarian",
+    "schinese",
+    "tchinese",
+    "czech",
+    "danish",
+    "dutch",
+    "english",
+    "finnish",
+    "french",
+    "german",
+    "greek",
+    "hungarian",
+    "indonesian",
+    "italian",
+    "japanese",
+    "koreana",
+    "norwegian",
+    "polish",
+    "portuguese",
+    "brazilian",
+    "romanian",
+    "russian",
+    "spanish",
+    "latam",
+    "swedish",
+    "thai",
+    "turkish",
+    "ukrainian",
+    "vietnamese",
+];
+
+pub fn check_language(language: &str) -> eyre::Result<()> {
+    if !STEAM_LANGUAGES.contains(&language) {
+        bail!(
+            "Unknown language `{language}`: Expected a Steam API language code, one of {}",
+            STEAM_LANGUAGES.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Localization files in the content directory's `workshop-localization` folder, keyed by language.
+fn localization_files(content_path: impl AsRef<Path>) -> eyre::Result<BTreeMap<String, PathBuf>> {
+    let localization_dir = content_path.as_ref().join(WORKSHOP_LOCALIZATION_DIR);
+    if !localization_dir.is_dir() {
+        return Ok(BTreeMap::new());
+    }
+
+    let mut files = BTreeMap::new();
+    for entry in fs_err::read_dir(localization_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension() != Some("toml".as_ref()) {
+            continue;
+        }
+        let language = path
+            .file_stem()
+            .and_then(|it| it.to_str())
+            .with_context(|| format!("Localization file name {:?} is not valid UTF-8", path))?
+            .to_owned();
+        files.insert(language, path);
+    }
+
+    Ok(files)
+}
+
+/// Collects the localizations from `workshop.toml` and the `workshop-localization` folder. A language can only be
+/// defined in one of them.
+pub fn load_localizations(
+    workshop_item_cfg: Option<&WorkshopItemConfig>,
+    content_path: impl AsRef<Path>,
+) -> eyre::Result<BTreeMap<String, LocalizationConfig>> {
+    let mut localizations = workshop_item_cfg
+        .map(|it| it.localization.clone())
+        .unwrap_or_default();
+
+    for (language, path) in localization_files(&content_path)? {
+        if localizations.contains_key(&language) {
+            bail!(
+                "Localization for `{language}` is defined in both `{WORKSHOP_METADATA_FILENAME}` and {:?}",
+                path
+            );
+        }
+        localizations.insert(language, LocalizationConfig::try_load_path(path)?);
+    }
+
+    for language in localizations.keys() {
+        check_language(language)?;
+    }
+
+    Ok(localizations)
+}
+
+/// One title and description update per language, to be submitted separately since Steam only takes a single
+/// language per update.
+///
+/// Descriptions are converted to Steam BBCode from `description_format`, the same as the item's own description.
+pub fn localized_updates(
+    localizations: &BTreeMap<String, LocalizationConfig>,
+    content_path: impl AsRef<Path>,
+    description_format: DescriptionFormat,
+) -> eyre::Result<Vec<ItemUpdate>> {
+    localizations
+        .iter()
+        .map(|(language, localization)| {
+            let description = match &localization.description_file {
+                Some(description_file) => Some(fs_err::read_to_string(
+                    content_path.as_ref().join(description_file),
+                )?),
+                None => localization.description.clone(),
+            };
+            Ok(ItemUpdate {
+                title: localization.title.clone(),
+                description: description.map(|it| convert_description(it, description_format)),
+                language: Some(language.clone()),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Overwrites the localizations, known locally or in `languages`, with the remote ones.
+///
+/// Localizations from the `workshop-localization` folder are written back to their files, new ones go to
+/// `workshop_item_cfg`. Local descriptions are kept as is if they're not in BBCode, like in [`pull_item_metadata`].
+pub fn pull_item_localizations(
+    backend: &dyn WorkshopBackend,
+    workshop_item_cfg: &mut WorkshopItemConfig,
+    content_path: impl AsRef<Path>,
+    languages: &[String],
+) -> eyre::Result<()> {
+    fn set_localized_text(
+        localization: &mut LocalizationConfig,
+        item: ItemDetails,
+        content_path: &Path,
+        keep_description: bool,
+    ) -> eyre::Result<()> {
+        localization.title = Some(item.title);
+        if keep_description {
+            return Ok(());
+        }
+        if let Some(description_file) = &localization.description_file {
+            fs_err::write(content_path.join(description_file), item.description)?;
+            localization.description = None;
+        } else {
+            localization.description = Some(item.description);
+        }
+        Ok(())
+    }
+
+    let keep_descriptions =
+        workshop_item_cfg.description_format.unwrap_or_default() != DescriptionFormat::Bbcode;
+    let files = localization_files(&content_path)?;
+    let all_languages = workshop_item_cfg
+        .localization
+        .keys()
+        .chain(files.keys())
+        .chain(languages)
+        .cloned()
+        .unique()
+        .collect_vec();
+
+    for language in all_languages {
+        check_language(&language)?;
+
+        let item = backend
+            .query_item_localized(workshop_item_cfg.item_id, &language)?
+            .with_context(|| {
+                format!(
+                    "Failed to receive query result for item id: {}",
+                    workshop_item_cfg.item_id
+                )
+            })?;
+
+        match files.get(&language) {
+            Some(path) => {
+                let mut localization = LocalizationConfig::try_load_path(path)?;
+                set_localized_text(
+                    &mut localization,
+                    item,
+                    content_path.as_ref(),
+                    keep_descriptions,
+                )?;
+                localization.store_path(path)?;
+            }
+            None => set_localized_text(
+                workshop_item_cfg.localization.entry(language).or_default(),
+                item,
+                content_path.as_ref(),
+                keep_descriptions,
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats a unix timestamp as `YYYY-MM-DD HH:MM UTC`.
+pub fn format_timestamp(timestamp: u32) -> String {
+    let days = timestamp as i64 / 86400;
+    let secs_of_day = timestamp as i64 % 86400;
+
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60
+    )
+}
+
+/// Reads the description from `--description-file` if given, and converts it to Steam BBCode according to its format.
+///
+/// To be called only once, after the description has been filled in from every other local source.
+pub fn render_description(workshop_item: &mut WorkshopItemArgs) -> eyre::Result<()> {
+    if let Some(description_file) = &workshop_item.description_file {
+        workshop_item.description = Some(fs_err::read_to_string(description_file)?);
+    }
+    workshop_item.description = workshop_item
+        .description
+        .take()
+        .map(|it| convert_description(it, workshop_item.description_format.unwrap_or_default()));
+    Ok(())
+}
+
+pub fn convert_description(description: String, format: DescriptionFormat) -> String {
+    match format {
+        DescriptionFormat::Bbcode => description,
+        DescriptionFormat::Markdown => markdown_to_bbcode(&description),
+    }
+}
+
+/// Overwrites the local metadata in `workshop_item_cfg` with the remote state of the item.
+///
+/// The description is written to the description file instead if one is set, as a path relative to `content_path`. It's
+/// left as is if it's not in BBCode, as it can't be converted back.
+pub fn pull_item_metadata(
+    item: ItemDetails,
+    workshop_item_cfg: &mut WorkshopItemConfig,
+    content_path: impl AsRef<Path>,
+) -> eyre::Result<()> {
+    workshop_item_cfg.tags = item
+        .tags
+        .into_iter()
+        .map(|it| Tag::new(it))
+        .collect::<eyre::Result<_>>()?;
+    workshop_item_cfg.title = Some(item.title);
+    workshop_item_cfg.visibility = Some(item.visibility);
+
+    if workshop_item_cfg.description_format.unwrap_or_default() != DescriptionFormat::Bbcode {
+        eprintln!(
+            "{}",
+            "[!] Keeping the local description, as the remote one can't be converted back from BBCode.".yellow()
+        );
+    } else if let Some(description_file) = &workshop_item_cfg.description_file {
+        fs_err::write(
+            content_path.as_ref().join(description_file),
+            item.description,
+        )?;
+        workshop_item_cfg.description = None;
+    } else {
+        workshop_item_cfg.description = Some(item.description);
+    }
+
+    Ok(())
+}
+
+/// Deletes the item from the Workshop, then removes or archives its metadata file if `content_path` is given. Its
+/// content manifest is removed either way.
+pub fn delete_item_with_metadata_file(
+    backend: &dyn WorkshopBackend,
+    item_id: u64,
+    content_path: Option<impl AsRef<Path>>,
+    archive_metadata: bool,
+) -> eyre::Result<()> {
+    backend.delete_item(item_id)?;
+
+    info!(item_id, "Workshop item deleted");
+
+    if let Some(content_path) = content_path {
+        let metadata_path = content_path.as_ref().join(WORKSHOP_METADATA_FILENAME);
+        if metadata_path.is_file() {
+            if archive_metadata {
+                fs_err::rename(
+                    &metadata_path,
+                    content_path
+                        .as_ref()
+                        .join(WORKSHOP_METADATA_ARCHIVE_FILENAME),
+                )?;
+            } else {
+                fs_err::remove_file(&metadata_path)?;
+            }
+        }
+
+        let manifest_path = content_path.as_ref().join(WORKSHOP_MANIFEST_FILENAME);
+        if manifest_path.is_file() {
+            fs_err::remove_file(&manifest_path)?;
+        }
+    }
+
+    Ok(())
+}
+
 pub fn open_workshop_page(item_id: u64) -> eyre::Result<()> {
     open::that(format!("steam://url/CommunityFilePage/{}", item_id))?;
     Ok(())